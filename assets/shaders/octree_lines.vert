@@ -0,0 +1,31 @@
+#version 330 core
+
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aColor;
+
+// camera basis of the raytracer, see CameraGetRay
+uniform vec3 origin;
+uniform vec3 horizontal;
+uniform vec3 vertical;
+uniform vec3 lower_left_corner;
+
+out vec3 color;
+
+// lines closer than this fraction of the viewport distance are clipped
+const float NEAR = 0.01;
+
+void main()
+{
+    color = aColor;
+
+    // perspective projection onto the viewport, written so that it is linear in aPos and lines clip correctly.
+    // w is 1 on the viewport plane and grows with the distance from the camera
+    vec3 plane_normal = cross(horizontal, vertical);
+    vec3 corner = lower_left_corner - origin;
+    vec3 to_point = aPos - origin;
+    float w = dot(to_point, plane_normal) / dot(corner, plane_normal);
+    vec3 on_plane = to_point - corner * w;
+    float u = dot(on_plane, horizontal) / dot(horizontal, horizontal);
+    float v = dot(on_plane, vertical) / dot(vertical, vertical);
+    gl_Position = vec4(2.0 * u - w, 2.0 * v - w, w - 2.0 * NEAR, w);
+}
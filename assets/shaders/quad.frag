@@ -5,7 +5,100 @@ in vec2 uv;
 
 uniform sampler2D ourTexture;
 
+// how the render texture is upscaled to the window
+#define UPSCALE_NEAREST 0
+#define UPSCALE_BILINEAR 1
+#define UPSCALE_EDGE_ADAPTIVE 2
+uniform int upscale_mode;
+
+vec3 fetch(ivec2 p) {
+    ivec2 size = textureSize(ourTexture, 0);
+    return texelFetch(ourTexture, clamp(p, ivec2(0), size - 1), 0).rgb;
+}
+
+float luma(vec3 c) {
+    return dot(c, vec3(0.299, 0.587, 0.114));
+}
+
+vec3 bilinear(vec2 uv) {
+    vec2 p = uv * vec2(textureSize(ourTexture, 0)) - 0.5;
+    ivec2 i = ivec2(floor(p));
+    vec2 f = p - floor(p);
+    vec3 bottom = mix(fetch(i), fetch(i + ivec2(1, 0)), f.x);
+    vec3 top = mix(fetch(i + ivec2(0, 1)), fetch(i + ivec2(1, 1)), f.x);
+    return mix(bottom, top, f.y);
+}
+
+float lanczos2(float x) {
+    const float PI = 3.14159265;
+    if (x < 1e-4) {
+        return 1.0;
+    }
+    if (x >= 2.0) {
+        return 0.0;
+    }
+    float px = PI * x;
+    return 2.0 * sin(px) * sin(px * 0.5) / (px * px);
+}
+
+// Simplified version of the FSR1 EASU pass: a lanczos kernel over the 4x4 texels around the sample that
+// is stretched along edges and narrowed across them, then clamped to the closest 2x2 texels to avoid ringing
+vec3 edge_adaptive(vec2 uv) {
+    vec2 p = uv * vec2(textureSize(ourTexture, 0)) - 0.5;
+    ivec2 base = ivec2(floor(p));
+    vec2 f = p - floor(p);
+
+    // luma gradient of the closest 2x2 texels, weighted by distance to the sample
+    vec2 grad = vec2(0.0);
+    for (int y = 0; y <= 1; y++) {
+        for (int x = 0; x <= 1; x++) {
+            ivec2 t = base + ivec2(x, y);
+            vec2 g = vec2(
+                luma(fetch(t + ivec2(1, 0))) - luma(fetch(t - ivec2(1, 0))),
+                luma(fetch(t + ivec2(0, 1))) - luma(fetch(t - ivec2(0, 1)))
+            );
+            float w = (x == 0 ? 1.0 - f.x : f.x) * (y == 0 ? 1.0 - f.y : f.y);
+            grad += g * w;
+        }
+    }
+    float edge_strength = length(grad);
+    vec2 across = edge_strength > 1e-5 ? grad / edge_strength : vec2(1.0, 0.0);
+    vec2 along = vec2(-across.y, across.x);
+    float stretch = 1.0 + clamp(edge_strength * 4.0, 0.0, 1.0);
+
+    vec3 color = vec3(0.0);
+    float weight_sum = 0.0;
+    vec3 near_min = vec3(1e20);
+    vec3 near_max = vec3(-1e20);
+    for (int y = -1; y <= 2; y++) {
+        for (int x = -1; x <= 2; x++) {
+            vec3 c = fetch(base + ivec2(x, y));
+            vec2 d = vec2(x, y) - f;
+            vec2 r = vec2(dot(d, across) * stretch, dot(d, along) / stretch);
+            float w = lanczos2(length(r));
+            color += c * w;
+            weight_sum += w;
+            if (x >= 0 && x <= 1 && y >= 0 && y <= 1) {
+                near_min = min(near_min, c);
+                near_max = max(near_max, c);
+            }
+        }
+    }
+    color /= max(weight_sum, 1e-5);
+    return clamp(color, near_min, near_max);
+}
+
 void main()
 {
-    Color = texture(ourTexture, uv);
-}
\ No newline at end of file
+    switch (upscale_mode) {
+        case UPSCALE_BILINEAR:
+            Color = vec4(bilinear(uv), 1.0);
+            break;
+        case UPSCALE_EDGE_ADAPTIVE:
+            Color = vec4(edge_adaptive(uv), 1.0);
+            break;
+        default:
+            Color = texture(ourTexture, uv);
+            break;
+    }
+}
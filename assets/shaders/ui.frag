@@ -0,0 +1,13 @@
+#version 330 core
+
+in vec2 uv;
+in vec4 color;
+
+out vec4 FragColor;
+
+uniform sampler2D ui_texture;
+
+void main()
+{
+    FragColor = color * texture(ui_texture, uv);
+}
@@ -0,0 +1,19 @@
+#version 330 core
+
+// egui vertices in points with a premultiplied gamma space color
+layout (location = 0) in vec2 aPos;
+layout (location = 1) in vec2 aUv;
+layout (location = 2) in vec4 aColor;
+
+uniform vec2 screen_size;
+
+out vec2 uv;
+out vec4 color;
+
+void main()
+{
+    uv = aUv;
+    color = aColor;
+    // points have origin in the top left corner
+    gl_Position = vec4(2.0 * aPos.x / screen_size.x - 1.0, 1.0 - 2.0 * aPos.y / screen_size.y, 0.0, 1.0);
+}
@@ -2,18 +2,18 @@ mod renderer;
 mod utility;
 mod resources;
 
-use glutin::{ContextWrapper, GlProfile, NotCurrent, dpi::PhysicalSize, event::{DeviceEvent, ElementState::{self, Pressed, Released}, Event, KeyboardInput, VirtualKeyCode::{self, *}, WindowEvent}, event_loop::ControlFlow, window::{Fullscreen, Window}};
+use glutin::{ContextWrapper, GlProfile, NotCurrent, dpi::PhysicalSize, event::{DeviceEvent, ElementState::{Pressed, Released}, Event, KeyboardInput, MouseScrollDelta, VirtualKeyCode::*, WindowEvent}, event_loop::ControlFlow, window::{Fullscreen, Window}};
 
 use cgmath::{Vector3};
-use std::{env, ffi::c_void, path::Path, sync::{Arc, Mutex, RwLock}, thread};
+use std::{env, ffi::c_void, path::{Path, PathBuf}, sync::{Arc, RwLock, mpsc}, thread, time::Instant};
 
 use resources::Resources;
-use renderer::{Material, camera::{CameraBuilder, CameraSettings}, compute_shader::ComputeShader, octree::{Octree}, program::Program, shader::Shader, vao::{
+use renderer::{camera::{CameraBuilder, CameraMode, CameraPoses, CameraSettings}, compute_shader::ComputeShader, debug_view::DebugView, gpu_timer::{GpuPass, GpuTimer}, atlas::TextureAtlas, material_editor::MaterialEditor, materials::{MaterialBuffers, MaterialLibrary}, octree::{EMPTY, LEAF, Octree}, octree_overlay::{self, OctreeOverlay}, temporal::TemporalAccumulation, program::Program, shader::Shader, ui_overlay, ui_painter::UiPainter, vao::{
         VertexArrayObject,
         VertexAttributePointer
     }, vbo::VertexBufferObject};
 
-use utility::{Direction, chronos::Chronos, ply_point_loader};
+use utility::{Direction, app_event::AppEvent, camera_path::CameraPath, chronos::Chronos, dynamic_resolution::DynamicResolution, frame_capture::FrameCapture, benchmark::Benchmark, input::{Action, InputBindings, InputState}, ply_point_loader, profiler::Profiler, ui_input::UiInput, walk::{WalkBody, WalkInput}};
 
 
 // TODO: currently lots of opengl stuff. Move all of it into renderer module
@@ -27,18 +27,39 @@ fn main() {
 
     let mut wb  = glutin::window::WindowBuilder::new()
         .with_title("TDT4230 Raytracer")
-        .with_resizable(false)
+        .with_resizable(true)
         .with_inner_size(physical_size)
         .with_always_on_top(true);
         
     let mut chronos: Chronos = Default::default();
 
-    let args: Vec<String> = env::args().collect();
-    for arg in args.iter().skip(1) {
+    let mut profile_csv: Option<PathBuf> = None;
+    let mut benchmark_scene: Option<String> = None;
+    let mut source_assets: Option<Resources> = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
         match &arg[..] {
             "-c" => {
                 chronos.display_fps = false
             }
+            "--profile-csv" => {
+                match args.next() {
+                    Some(path) => profile_csv = Some(PathBuf::from(path)),
+                    None => eprintln!("--profile-csv expects a file path"),
+                }
+            },
+            "--benchmark" => {
+                match args.next() {
+                    Some(scene) => benchmark_scene = Some(scene),
+                    None => eprintln!("--benchmark expects a scene name"),
+                }
+            },
+            "--save-to-source" => {
+                match args.next() {
+                    Some(path) => source_assets = Some(Resources::from_path(Path::new(&path))),
+                    None => eprintln!("--save-to-source expects the assets folder of the source tree"),
+                }
+            },
             "-f" | "-F" => {
                 wb = wb.with_maximized(true)
                     .with_fullscreen(Some(Fullscreen::Borderless(el.primary_monitor())));
@@ -48,17 +69,22 @@ fn main() {
                 let c_command = "\n-c => 'turn off fps display in terminal'";
                 let h_command = "\n-h => 'display this information'";
                 let f_command = "\n-f | -F => 'fullscreen mode'"; 
-                println!("Rendering toy code{}{}{}", h_command, f_command, c_command);
+                let p_command = "\n--profile-csv <path> => 'log frame and gpu pass timings to a csv file'";
+                let b_command = "\n--benchmark <scene> => 'play assets/benchmarks/<scene>.ron and write a report to benchmarks/'";
+                let s_command = "\n--save-to-source <assets> => 'also save the materials to <assets>/settings/materials.ron'";
+                println!("Rendering toy code{}{}{}{}{}{}", h_command, f_command, c_command, p_command, b_command, s_command);
                 return;
             },
             c => eprintln!("Unknown command '{}'", c)
         }
     }
 
+    // frame times are not limited by the display when benchmarking
+    let vsync = benchmark_scene.is_none();
     let cb = glutin::ContextBuilder::new()
-        .with_gl_profile(GlProfile::Core).with_vsync(true);
+        .with_gl_profile(GlProfile::Core).with_vsync(vsync);
     
-    let windowed_context = cb.with_vsync(true).build_windowed(wb, &el).unwrap();
+    let windowed_context = cb.with_vsync(vsync).build_windowed(wb, &el).unwrap();
     {
         // This seems to fail at random on X11, so try a couple of times before failing
         const MAX_GRAB_ATTEMPTS: u32 = 20;
@@ -76,30 +102,13 @@ fn main() {
 
     windowed_context.window().set_cursor_visible(false);
 
-    // Set up a shared vector for keeping track of currently pressed keys
-    let arc_pressed_keys = Arc::new(Mutex::new(Vec::<VirtualKeyCode>::with_capacity(10)));
-    // Make a reference of this vector to send to the render thread
-    let pressed_keys = Arc::clone(&arc_pressed_keys);
-
-    // Set up shared tuple for tracking mouse movement between frames
-    let arc_mouse_delta = Arc::new(Mutex::new((0f32, 0f32)));
-    // Make a reference of this tuple to send to the render thread
-    let mouse_delta = Arc::clone(&arc_mouse_delta);
-
-    #[derive(Clone, Copy)]
-    enum ClickEvent {
-        None = 2,
-        Left = 1,
-        Right = 0
-    }
-    // Set up shared device event storage
-    let arc_left_mouse_events = Arc::new(Mutex::new(ClickEvent::None));
-    // Make a reference of this tuple to send to the render thread
-    let arc_left_mouse = Arc::clone(&arc_left_mouse_events);
+    // Input, window and settings events are sent to the render thread in the order they happen
+    let (event_sender, event_receiver) = mpsc::channel::<AppEvent>();
+    // Make a sender for the settings watcher in the render thread
+    let watcher_sender = event_sender.clone();
 
-    let sf = windowed_context.window().scale_factor();
-    
-    let logical_dimensions = windowed_context.window().inner_size().to_logical::<i32>(sf);
+    // the window might not get the requested size, e.g. in fullscreen
+    let window_size = windowed_context.window().inner_size();
 
     // Spawn a separate thread for rendering, so event handling doesn't block rendering
     let render_thread = thread::spawn(move || {
@@ -111,12 +120,12 @@ fn main() {
             c
         };
         unsafe {
-            gl::Viewport(0, 0, physical_size.width, physical_size.height); // set viewport
+            gl::Viewport(0, 0, window_size.width as i32, window_size.height as i32); // set viewport
             gl::ClearColor(0.0, 0.0, 0.0, 1.0);
         }
 
         // create quad data
-        let quad_program = Program::from_resources(&res, "shaders/quad").unwrap();
+        let mut quad_program = Program::from_resources(&res, "shaders/quad").unwrap();
         let quad_indices = VertexBufferObject::new::<u32>(
             vec![
                 0, 1, 2,
@@ -159,52 +168,93 @@ fn main() {
             ComputeShader::new(program).unwrap() // TODO: handle this
         }; 
         
-        let camera_config_changed = Arc::new(Mutex::new(false));
-        let camera_config_changed_render = Arc::clone(&camera_config_changed);
+        const CAMERA_POSES_PATH: &str = "settings/camera_poses.ron";
+        let mut camera_poses = CameraPoses::from_resources(&res, CAMERA_POSES_PATH);
+
+        const CAMERA_PATH_PATH: &str = "settings/camera_path.ron";
+        let mut camera_path = CameraPath::from_resources(&res, CAMERA_PATH_PATH).unwrap_or_default();
+        // keyframes are spaced by the time between adding them, the first one added to a loaded or played path
+        // is KEYFRAME_GAP seconds after the last keyframe
+        const KEYFRAME_GAP: f32 = 2.0;
+        let mut last_keyframe_instant: Option<Instant> = None;
+        // playback time of the camera path and optional frame capture of the playback
+        let mut path_playback: Option<(f32, Option<FrameCapture>)> = None;
+
+        const CAMERA_SETTINGS_PATH: &str = "settings/camera.ron";
+        const INPUT_BINDINGS_PATH: &str = "settings/input.ron";
+        let mut input_bindings = InputBindings::from_resources(&res, INPUT_BINDINGS_PATH).unwrap_or_else(|e| {
+            eprintln!("{}, using default bindings", e);
+            InputBindings::default()
+        });
+
         let mut camera = {
-            let mut builder = CameraBuilder::new(90.0, logical_dimensions.width as i32);
-            builder.with_aspect_ratio(logical_dimensions.width as f32 / logical_dimensions.height as f32)
+            let mut builder = CameraBuilder::new(90.0, window_size.width as i32);
+            builder.with_aspect_ratio(window_size.width as f32 / window_size.height as f32)
                 .with_origin(Vector3::<f32>::new(0.0, -0.1, -0.3))
                 .with_viewport_height(2.0); 
 
-            if let Ok(bytes) = res.load_buffer("settings/camera.ron") {
-                let settings: CameraSettings = ron::de::from_bytes(&bytes[0..]).unwrap();
-                builder.with_sample_per_pixel(settings.samples_per_pixel)
-                    .with_max_bounce(settings.max_bounce)
-                    .with_turn_rate(settings.turn_rate)
-                    .with_normal_speed(settings.normal_speed)
-                    .with_sprint_speed(settings.sprint_speed);
-
-                let watch_path = res.to_abs_path("settings");
-                let _camera_watcher = thread::spawn(move || {
-                    use std::sync::mpsc;
-                    use std::time;
-                    use notify::{Watcher, DebouncedEvent};
-        
-                    let (tx, rx) = mpsc::channel();
-                    let mut watcher = notify::watcher(tx, time::Duration::from_secs_f32(1.0)).unwrap();
-                    watcher.watch(watch_path, notify::RecursiveMode::Recursive).unwrap();
-        
-                    loop {
-                        match rx.recv() {
-                            Ok(event) => {
-                                match event {
-                                    DebouncedEvent::Write(p) => {
-                                        if p.ends_with("camera.ron") {
-                                            println!("Camera settings changed");
-                                            if let Ok(mut v) = camera_config_changed.lock() {
-                                                *v = true;
-                                            }
-                                        }
+            if let Some(pose) = camera_poses.last {
+                builder.with_pose(pose);
+            }
+
+            match CameraSettings::from_resources(&res, CAMERA_SETTINGS_PATH) {
+                Ok(settings) => {
+                    builder.with_sample_per_pixel(settings.samples_per_pixel)
+                        .with_max_bounce(settings.max_bounce)
+                        .with_turn_rate(settings.turn_rate)
+                        .with_normal_speed(settings.normal_speed)
+                        .with_sprint_speed(settings.sprint_speed)
+                        .with_projection(settings.projection)
+                        .with_ortho_scale(settings.ortho_scale)
+                        .with_fisheye_fov(settings.fisheye_fov)
+                        .with_max_pitch(settings.max_pitch)
+                        .with_roll(settings.allow_roll)
+                        .with_zoom(settings.zoom_rate, settings.min_fov, settings.max_fov)
+                        .with_up(settings.up)
+                        .with_orbit(settings.orbit_zoom_rate, settings.turntable_speed)
+                        .with_walk(settings.walk)
+                        .with_render_scale(settings.render_scale)
+                        .with_upscale(settings.upscale)
+                        .with_dynamic_resolution(settings.dynamic_resolution)
+                        .with_temporal(settings.temporal)
+                        .with_fog(settings.fog)
+                        .with_shading(settings.shading);
+                },
+                Err(e) => eprintln!("{}, using default camera settings", e),
+            }
+
+            let watch_path = res.to_abs_path("settings");
+            let _camera_watcher = thread::spawn(move || {
+                use std::time;
+                use notify::{Watcher, DebouncedEvent};
+    
+                let (tx, rx) = mpsc::channel();
+                let mut watcher = notify::watcher(tx, time::Duration::from_secs_f32(1.0)).unwrap();
+                watcher.watch(watch_path, notify::RecursiveMode::Recursive).unwrap();
+    
+                loop {
+                    match rx.recv() {
+                        Ok(event) => {
+                            match event {
+                                DebouncedEvent::Write(p) => {
+                                    if p.ends_with("camera.ron") {
+                                        println!("Camera settings changed");
+                                        let _ = watcher_sender.send(AppEvent::CameraSettingsChanged);
+                                    } else if p.ends_with("input.ron") {
+                                        println!("Input bindings changed");
+                                        let _ = watcher_sender.send(AppEvent::InputBindingsChanged);
+                                    } else if p.ends_with("materials.ron") {
+                                        println!("Materials changed");
+                                        let _ = watcher_sender.send(AppEvent::MaterialsChanged);
                                     }
-                                    _ => (),
                                 }
-                            },
-                            Err(e) => eprintln!("watch error: {:?}", e),
-                        }
+                                _ => (),
+                            }
+                        },
+                        Err(e) => eprintln!("watch error: {:?}", e),
                     }
-                });
-            } 
+                }
+            });
 
             builder.build(&mut raytrace_program.program).unwrap()
         };
@@ -229,10 +279,32 @@ fn main() {
             ComputeShader::new(program).unwrap() // TODO: handle this
         }; 
 
+        const MATERIALS_PATH: &str = "settings/materials.ron";
+        let mut material_library = match MaterialLibrary::from_resources(&res, MATERIALS_PATH) {
+            Ok(library) => library,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        let mut material_buffers = MaterialBuffers::new(&material_library);
+        let mut texture_atlas = match TextureAtlas::from_library(&res, &material_library) {
+            Ok(atlas) => atlas,
+            Err(e) => {
+                eprintln!("Invalid atlas in {}: {}", MATERIALS_PATH, e);
+                return;
+            }
+        };
+        if let Err(e) = TextureAtlas::apply(texture_atlas.as_ref(), &mut raytrace_program.program) {
+            eprintln!("{}", e);
+        }
+
         // TODO: vao might not be needed for shader storage buffer? read spec 
         //       and update code accordingly
-        let octree = { 
+        let mut octree = { 
             const PRE_ALLOCATED_CELLS: usize = 100000;
+            let host_cells;
+            let cells_buffer;
             let vao = {
                 use renderer::octree::{EMPTY, PARENT, LEAF};
                 let mut allocated_cells =  Vec::<u32>::with_capacity(PRE_ALLOCATED_CELLS * 8 * 2);
@@ -340,6 +412,8 @@ fn main() {
                     allocated_cells.push(EMPTY);
                 }
     
+                // the host keeps a copy to answer collision queries in walk mode
+                host_cells = allocated_cells.clone();
                 let cells_vbo = VertexBufferObject::new::<u32>(
                     allocated_cells,
                     gl::ARRAY_BUFFER,
@@ -352,107 +426,13 @@ fn main() {
                     offset: 0
                 };
                 unsafe { gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, cells_vbo.id()); } 
+                cells_buffer = cells_vbo.id();
                 VertexArrayObject::new::<u32>(vec![cells_attrib], cells_vbo.id(), gl::UNSIGNED_INT)
             };
         
-            {
-                let lambe = Material::Lambertian as u32;
-                let diele = Material::Dielectric as u32;
-                let metal = Material::Metal as u32;
-                let mat_vbo = VertexBufferObject::new::<u32>(
-                    vec![
-                    // |Type  |Attrib |Albedo |
-                        lambe,  0,      0,
-                        lambe,  0,      1, 
-                        diele,  0,      2,  
-                        metal,  0,      3,
-                        metal,  1,      4,
-                        metal,  2,      5,
-                        metal,  3,      6,
-                        diele,  0,      4,  
-                        diele,  0,      5, 
-                        lambe,  0,      6, 
-                        lambe,  0,      5,
-                        lambe,  0,      4,
-                        lambe,  0,      3, 
-                    ],
-                    gl::ARRAY_BUFFER,
-                    gl::STATIC_DRAW
-                );
-                unsafe { gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 1, mat_vbo.id()); } 
-                let mat_attrib = VertexAttributePointer {
-                    location: 0,
-                    size: 3,
-                    offset: 0
-                };
-                vao.append_vbo::<u32>(vec![mat_attrib], mat_vbo.id(), gl::UNSIGNED_INT);
-            }
-            
-            {
-                let albedo_vbo = VertexBufferObject::new::<f32>(
-                    vec![
-                    // |Albedo        
-                        0.1, 0.2, 0.5, 
-                        0.8, 0.8, 0.0,
-                        0.8, 0.8, 0.8,
-                        0.8, 0.6, 0.2,
-                        0.2, 0.4, 0.8,
-                        0.4, 0.8, 0.2,
-                        0.2, 0.2, 0.2,
-                    ],
-                    gl::ARRAY_BUFFER,
-                    gl::STATIC_DRAW
-                );
-                let mat_attrib = VertexAttributePointer {
-                    location: 5,
-                    size: 3,
-                    offset: 0
-                };
-                unsafe { gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 2, albedo_vbo.id()); } 
-                vao.append_vbo::<f32>(vec![mat_attrib], albedo_vbo.id(), gl::FLOAT);
-            }
-
-            {
-                let metal_vbo = VertexBufferObject::new::<f32>(
-                    vec![
-                    // |Fuzz |
-                        0.1,
-                        0.3,
-                        0.4,
-                        0.8,
-                    ],
-                    gl::ARRAY_BUFFER,
-                    gl::STATIC_DRAW
-                );
-                let metal_attrib = VertexAttributePointer {
-                    location: 6,
-                    size: 1,
-                    offset: 0
-                };
-                unsafe { gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 3, metal_vbo.id()); } 
-                vao.append_vbo::<f32>(vec![metal_attrib], metal_vbo.id(), gl::FLOAT);
-            }
-
-            {
-                let dielectric_vbo = VertexBufferObject::new::<f32>(
-                    vec![
-                    // |Ir |
-                        1.2,
-                    ],
-                    gl::ARRAY_BUFFER,
-                    gl::STATIC_DRAW
-                );
-                let dielectric_attrib = VertexAttributePointer {
-                    location: 7,
-                    size: 1,
-                    offset: 0
-                };
-                unsafe { gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 4, dielectric_vbo.id()); } 
-                vao.append_vbo::<f32>(vec![dielectric_attrib], dielectric_vbo.id(), gl::FLOAT);
-            }
 
            
-            let o = Octree::new(
+            let mut o = Octree::new(
                 Vector3::new(-0.5, -0.5, -1.0), 
                 1.0, 
                 10, 
@@ -461,6 +441,7 @@ fn main() {
                 100, 
                 vao
             ).unwrap();
+            o.mirror_cells(host_cells, cells_buffer);
 
             if let Err(e) = o.init_global_buffers() { 
                 eprintln!("{}", e);
@@ -468,6 +449,10 @@ fn main() {
             }
             o
         };
+        if let Some(Err(e)) = octree.host.max_leaf_value().map(|i| material_library.check_index(i)) {
+            eprintln!("Invalid material in {}: {}", MATERIALS_PATH, e);
+            return;
+        }
 
         // TODO: remove 
         let mut delta = Vec::<f32>::with_capacity(100 * 4);
@@ -479,106 +464,570 @@ fn main() {
             delta.push(0.0);
         }
 
+        // roll and zoom speeds when using keys, in the same units as mouse movement and scroll steps
+        const KEY_ROLL_SPEED: f32 = 20.0;
+        const KEY_ZOOM_SPEED: f32 = 10.0;
+
         let click_cooldown = 0.05;
         let mut last_click_count = 0.0;
         let mut active_voxel = 0;
-        let render_size = (camera.render_texture.width(), camera.render_texture.height(), camera.render_texture.depth());
+        let mut input_state = InputState::default();
+        let mut turntable = false;
+        let mut walk_body = WalkBody::default();
+        let mut dynamic_resolution = DynamicResolution::new(camera.render_scale(), camera.settings.samples_per_pixel);
+        quad_program.set_i32("upscale_mode", camera.settings.upscale as i32).unwrap();
+        let mut temporal = TemporalAccumulation::new(camera.render_texture.width(), camera.render_texture.height()).unwrap();
+
+        let mut screen_size = (window_size.width as i32, window_size.height as i32);
+        let mut gpu_timer = GpuTimer::new();
+        let mut profiler = Profiler::new(profile_csv.as_deref()).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            Profiler::new(None).unwrap()
+        });
+        let mut show_profiler = false;
+        let mut debug_view = DebugView::Off;
+        let mut show_octree_overlay = false;
+        let mut material_editor = MaterialEditor::default();
+        let mut show_material_editor = false;
+        // placing and removing voxels paints and picks materials instead
+        let mut paint_mode = false;
+        // palette, camera settings and stats panels
+        let mut show_settings_ui = false;
+        // the camera looks around with the mouse while it is grabbed, the ui gets the cursor otherwise
+        let mut mouse_grabbed = true;
+        let mut ui_input = UiInput::default();
+        let mut octree_overlay = match OctreeOverlay::from_resources(&res, 4) {
+            Ok(o) => o,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        let ui_context = egui::Context::default();
+        let mut ui_painter = match UiPainter::from_resources(&res) {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        let start_instant = Instant::now();
+
+        // benchmark mode ignores user input and settings changes
+        let mut benchmark = match &benchmark_scene {
+            Some(name) => match Benchmark::from_resources(&res, name) {
+                Ok(b) => Some(b),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            },
+            None => None,
+        };
+        if let Some(b) = &benchmark {
+            let scene = b.scene();
+            let mut settings = camera.settings.clone();
+            settings.samples_per_pixel = scene.samples_per_pixel;
+            settings.max_bounce = scene.max_bounce;
+            settings.render_scale = 1.0;
+            settings.dynamic_resolution.enabled = false;
+            settings.temporal = scene.temporal;
+            settings.fog = scene.fog;
+            settings.shading = scene.shading;
+            settings.upscale = scene.upscale;
+            camera.apply_settings(&mut raytrace_program.program, settings);
+            quad_program.set_i32("upscale_mode", camera.settings.upscale as i32).unwrap();
+            if let Err(e) = DebugView::Off.apply(&mut raytrace_program.program) {
+                eprintln!("{}", e);
+            }
+            if let Err(e) = camera.resize(&mut raytrace_program.program, scene.resolution.0, scene.resolution.1) {
+                eprintln!("failed to resize render texture: {}", e);
+                return;
+            }
+            raytrace_program.program.set_i32("seed", scene.seed).unwrap();
+            println!("Running benchmark {} for {} frames", benchmark_scene.as_deref().unwrap_or_default(), scene.frames);
+        }
         loop {
             chronos.tick();
+            // chronos measures the previous frame, the benchmark measures this one
+            let frame_start = Instant::now();
 
-            if let Ok(change) = camera_config_changed_render.lock() {
-                if *change {
-                    // TODO: really bad idea to do blocking io in render thread ...
-                    if let Ok(bytes) = res.load_buffer("settings/camera.ron") {
-                        match ron::de::from_bytes::<CameraSettings>(&bytes[0..]) {
-                            Ok(settings) => camera.apply_settings(&mut raytrace_program.program, settings),
-                            Err(_) => (),
+            for event in event_receiver.try_iter() {
+                match event {
+                    AppEvent::Exit => {
+                        camera_poses.last = Some(camera.pose());
+                        camera_poses.save(&res, CAMERA_POSES_PATH);
+                        return;
+                    },
+                    AppEvent::CameraSettingsChanged if benchmark.is_none() => {
+                        // TODO: really bad idea to do blocking io in render thread ...
+                        // the previous settings are kept when the file is invalid
+                        match CameraSettings::from_resources(&res, CAMERA_SETTINGS_PATH) {
+                            Ok(settings) => {
+                                camera.apply_settings(&mut raytrace_program.program, settings);
+                                quad_program.set_i32("upscale_mode", camera.settings.upscale as i32).unwrap();
+                                // start over from the configured quality
+                                dynamic_resolution = DynamicResolution::new(camera.render_scale(), camera.settings.samples_per_pixel);
+                            },
+                            Err(e) => eprintln!("{}", e),
                         }
-                        
-                    }
+                    },
+                    AppEvent::MaterialsChanged => {
+                        let library = MaterialLibrary::from_resources(&res, MATERIALS_PATH).and_then(|library| {
+                            match octree.host.max_leaf_value() {
+                                Some(index) => library.check_index(index)
+                                    .map_err(|e| format!("Invalid material in {}: {}", MATERIALS_PATH, e))
+                                    .map(|_| library),
+                                None => Ok(library),
+                            }
+                        }).and_then(|library| {
+                            TextureAtlas::from_library(&res, &library)
+                                .map_err(|e| format!("Invalid atlas in {}: {}", MATERIALS_PATH, e))
+                                .map(|atlas| (library, atlas))
+                        });
+                        match library {
+                            Ok((library, atlas)) => {
+                                material_buffers.upload(&library);
+                                material_library = library;
+                                // a shorter library might not have the placed material, the library is never empty
+                                if material_library.check_index(active_voxel).is_err() {
+                                    active_voxel = material_library.materials.len() as u32 - 1;
+                                    println!("Placing {}", material_library.materials[active_voxel as usize].name);
+                                }
+                                material_editor.select(active_voxel);
+                                material_editor.saved();
+                                texture_atlas = atlas;
+                                if let Err(e) = TextureAtlas::apply(texture_atlas.as_ref(), &mut raytrace_program.program) {
+                                    eprintln!("{}", e);
+                                }
+                                temporal.invalidate();
+                            },
+                            Err(e) => eprintln!("{}, keeping the previous materials", e),
+                        }
+                    },
+                    AppEvent::InputBindingsChanged => {
+                        match InputBindings::from_resources(&res, INPUT_BINDINGS_PATH) {
+                            Ok(bindings) => {
+                                input_bindings = bindings;
+                                input_state.update_actions(&input_bindings);
+                            },
+                            Err(e) => eprintln!("{}", e),
+                        }
+                    },
+                    // a minimized window has no size, keep rendering at the old size
+                    AppEvent::Resized(width, height) => if width > 0 && height > 0 {
+                        context.resize(PhysicalSize::new(width, height));
+                        screen_size = (width as i32, height as i32);
+                        unsafe { gl::Viewport(0, 0, width as i32, height as i32); }
+                        if benchmark.is_none() {
+                            if let Err(e) = camera.resize(&mut raytrace_program.program, width as i32, height as i32) {
+                                eprintln!("failed to resize render texture: {}", e);
+                            }
+                        }
+                    },
+                    input_event => if benchmark.is_none() {
+                        if !mouse_grabbed {
+                            ui_input.handle(&input_event, context.window().scale_factor() as f32);
+                        }
+                        input_state.handle(&input_event, &input_bindings);
+                    },
                 }
             }
 
-            // TODO: all these events should be a application specific enum to avoid all of these mutexes
-            // Handle keyboard input
-            if let Ok(keys) = pressed_keys.lock() {
-                let mut l_shift_used = false;
-                for key in keys.iter() {
-                    match key {
-                        VirtualKeyCode::W           => camera.translate(&mut raytrace_program.program, &Direction::Front.into_vector3(), chronos.delta_time()),
-                        VirtualKeyCode::A           => camera.translate(&mut raytrace_program.program, &Direction::Left.into_vector3(),  chronos.delta_time()),
-                        VirtualKeyCode::S           => camera.translate(&mut raytrace_program.program, &Direction::Back.into_vector3(),  chronos.delta_time()),
-                        VirtualKeyCode::D           => camera.translate(&mut raytrace_program.program, &Direction::Rigth.into_vector3(), chronos.delta_time()),
-                        VirtualKeyCode::Space       => camera.translate(&mut raytrace_program.program, &Direction::Up.into_vector3(),    chronos.delta_time()),
-                        VirtualKeyCode::LControl    => camera.translate(&mut raytrace_program.program, &Direction::Down.into_vector3(),  chronos.delta_time()),
-                        VirtualKeyCode::G           => octree.update_vbo(&delta, delta.len(), &octree_update_program),
-                        VirtualKeyCode::Key1        => active_voxel = 0,
-                        VirtualKeyCode::Key2        => active_voxel = 1,
-                        VirtualKeyCode::Key3        => active_voxel = 2,
-                        VirtualKeyCode::Key4        => active_voxel = 3,
-                        VirtualKeyCode::Key5        => active_voxel = 4,
-                        VirtualKeyCode::Key6        => active_voxel = 6,
-                        VirtualKeyCode::Key7        => active_voxel = 7,
-                        VirtualKeyCode::Key8        => active_voxel = 8,
-                        VirtualKeyCode::Key9        => active_voxel = 9,
-                        VirtualKeyCode::LShift      => {
+            {
+                let mut sprint = false;
+                let mut move_input = Vector3::new(0.0, 0.0, 0.0);
+                let mut jump = false;
+                for action in input_state.held().iter() {
+                    match action {
+                        Action::MoveForward     => move_input += Direction::Front.into_vector3(),
+                        Action::MoveLeft        => move_input += Direction::Left.into_vector3(),
+                        Action::MoveBack        => move_input += Direction::Back.into_vector3(),
+                        Action::MoveRight       => move_input += Direction::Rigth.into_vector3(),
+                        Action::MoveUp          => {
+                            move_input += Direction::Up.into_vector3();
+                            jump = true;
+                        },
+                        Action::MoveDown        => move_input += Direction::Down.into_vector3(),
+                        Action::RollLeft        => camera.turn_roll(&mut raytrace_program.program,  chronos.delta_time() as f32 * KEY_ROLL_SPEED),
+                        Action::RollRight       => camera.turn_roll(&mut raytrace_program.program, -chronos.delta_time() as f32 * KEY_ROLL_SPEED),
+                        Action::ZoomIn          => camera.zoom(&mut raytrace_program.program,  chronos.delta_time() as f32 * KEY_ZOOM_SPEED),
+                        Action::ZoomOut         => camera.zoom(&mut raytrace_program.program, -chronos.delta_time() as f32 * KEY_ZOOM_SPEED),
+                        Action::DebugUpdate     => {
+                            gpu_timer.begin(GpuPass::OctreeUpdate);
+                            octree.update_vbo(&delta, delta.len(), &octree_update_program);
+                            gpu_timer.end(GpuPass::OctreeUpdate);
+                            octree_overlay.invalidate();
+                        },
+                        Action::EditorIncrease | Action::EditorDecrease if show_material_editor => {
+                            let direction = if *action == Action::EditorIncrease { 1.0 } else { -1.0 };
+                            if material_editor.adjust(&mut material_library, direction * chronos.delta_time() as f32, false) {
+                                material_buffers.upload(&material_library);
+                                temporal.invalidate();
+                            }
+                        },
+                        Action::SelectVoxel(i)  => match material_library.check_index(*i) {
+                            Ok(()) => active_voxel = *i,
+                            Err(e) => eprintln!("{}", e),
+                        },
+                        Action::Sprint          => {
                             camera.set_speed_to_sprint();
-                            l_shift_used = true;
+                            sprint = true;
                         },
                         _ => { }
                     }
                 }
-                if !l_shift_used {
+                if !sprint {
                     camera.set_speed_to_normal();
                 }
+
+                match camera.mode() {
+                    CameraMode::Walk => {
+                        let input = WalkInput {
+                            direction: camera.walk_direction(&Vector3::new(move_input.x, 0.0, move_input.z)),
+                            jump,
+                            sprint,
+                        };
+                        let eye = walk_body.step(&camera.settings.walk, &octree, camera.origin, &input, chronos.delta_time() as f32);
+                        camera.set_origin(&mut raytrace_program.program, eye);
+                    },
+                    _ => if move_input != Vector3::new(0.0, 0.0, 0.0) {
+                        camera.translate(&mut raytrace_program.program, &move_input, chronos.delta_time());
+                    },
+                }
+
+                // Actions that only trigger once per press
+                for action in input_state.pressed().iter() {
+                    match action {
+                        Action::JumpToBookmark(slot) => if let Some(pose) = camera_poses.bookmarks.get(slot) {
+                            camera.set_pose(&mut raytrace_program.program, pose);
+                        },
+                        Action::StoreBookmark(slot) => {
+                            camera_poses.bookmarks.insert(*slot, camera.pose());
+                            camera_poses.save(&res, CAMERA_POSES_PATH);
+                            println!("Stored camera bookmark {}", slot);
+                        },
+                        Action::AddKeyframe => {
+                            let time = match camera_path.keyframes.last() {
+                                Some(k) => k.time + last_keyframe_instant.map_or(KEYFRAME_GAP, |i| i.elapsed().as_secs_f32()),
+                                None => 0.0,
+                            };
+                            last_keyframe_instant = Some(Instant::now());
+                            camera_path.push(time, camera.pose());
+                            if let Err(e) = camera_path.save(&res, CAMERA_PATH_PATH) {
+                                eprintln!("{}", e);
+                            }
+                            println!("Added camera keyframe {} at {:.2}s", camera_path.keyframes.len(), time);
+                        },
+                        Action::ClearPath => {
+                            camera_path.keyframes.clear();
+                            path_playback = None;
+                            if let Err(e) = camera_path.save(&res, CAMERA_PATH_PATH) {
+                                eprintln!("{}", e);
+                            }
+                            println!("Cleared camera path");
+                        },
+                        Action::ToggleOrbit => {
+                            match camera.mode() {
+                                CameraMode::Free | CameraMode::Walk => {
+                                    let center = octree.min_point() + Vector3::new(0.5, 0.5, 0.5) * octree.scale();
+                                    camera.enter_orbit(&mut raytrace_program.program, center);
+                                },
+                                CameraMode::Orbit { .. } => {
+                                    camera.enter_free();
+                                    turntable = false;
+                                },
+                            }
+                        },
+                        Action::FocusOrbit => match octree_overlay::crosshair_hit(&octree, &camera) {
+                            Some((_, point)) => {
+                                let target = octree.min_point() + point * octree.block_distance();
+                                camera.enter_orbit(&mut raytrace_program.program, target);
+                                turntable = false;
+                            },
+                            None => println!("No voxel under the crosshair"),
+                        },
+                        Action::ToggleTurntable if matches!(camera.mode(), CameraMode::Orbit { .. }) => turntable = !turntable,
+                        Action::ToggleWalk => {
+                            // toggle between walking and flying
+                            walk_body.reset();
+                            match camera.mode() {
+                                CameraMode::Walk => camera.enter_free(),
+                                _ => match camera.enter_walk() {
+                                    Ok(()) => turntable = false,
+                                    Err(e) => eprintln!("{}", e),
+                                },
+                            }
+                        },
+                        Action::NextVoxel | Action::PreviousVoxel => {
+                            let count = material_library.materials.len() as u32;
+                            let step = if *action == Action::NextVoxel { 1 } else { count - 1 };
+                            active_voxel = (active_voxel + step) % count;
+                            println!("Placing {}", material_library.materials[active_voxel as usize].name);
+                        },
+                        Action::ToggleProfiler => show_profiler = !show_profiler,
+                        Action::NextDebugView => {
+                            debug_view = debug_view.next();
+                            if let Err(e) = debug_view.apply(&mut raytrace_program.program) {
+                                eprintln!("{}", e);
+                            }
+                            // debug views do not write the history
+                            temporal.invalidate();
+                            println!("Debug view: {}", debug_view.name());
+                        },
+                        Action::ToggleSmoothNormals => {
+                            camera.settings.shading.smooth_normals = !camera.settings.shading.smooth_normals;
+                            if let Err(e) = camera.settings.shading.apply(&mut raytrace_program.program) {
+                                eprintln!("{}", e);
+                            }
+                            temporal.invalidate();
+                            println!("Smooth normals: {}", camera.settings.shading.smooth_normals);
+                        },
+                        Action::ToggleOctreeOverlay => show_octree_overlay = !show_octree_overlay,
+                        Action::ToggleMaterialEditor => {
+                            show_material_editor = !show_material_editor;
+                            material_editor.select(active_voxel);
+                        },
+                        Action::PickMaterial => match octree_overlay::crosshair_hit(&octree, &camera) {
+                            Some((node, _)) if material_library.check_index(node.value).is_ok() => {
+                                active_voxel = node.value;
+                                material_editor.select(node.value);
+                                show_material_editor = true;
+                                println!("Picked {}", material_library.materials[node.value as usize].name);
+                            },
+                            _ => println!("No material under the crosshair"),
+                        },
+                        Action::EditorNextField | Action::EditorPreviousField if show_material_editor => {
+                            let step = if *action == Action::EditorNextField { 1 } else { -1 };
+                            material_editor.step_field(&material_library, step);
+                        },
+                        // the kind changes once per press
+                        Action::EditorIncrease | Action::EditorDecrease if show_material_editor => {
+                            let direction = if *action == Action::EditorIncrease { 1.0 } else { -1.0 };
+                            if material_editor.adjust(&mut material_library, direction, true) {
+                                material_buffers.upload(&material_library);
+                                temporal.invalidate();
+                            }
+                        },
+                        // the copy next to the executable is replaced by the source file on the next build
+                        Action::SaveMaterials => {
+                            let saved = material_library.save(&res, MATERIALS_PATH).and_then(|_| {
+                                match &source_assets {
+                                    Some(source) => material_library.save(source, MATERIALS_PATH)
+                                        .map(|_| source.to_abs_path(MATERIALS_PATH)),
+                                    None => Ok(res.to_abs_path(MATERIALS_PATH)),
+                                }
+                            });
+                            match saved {
+                                Ok(path) => {
+                                    material_editor.saved();
+                                    println!("Saved {:?}", path);
+                                },
+                                Err(e) => eprintln!("{}", e),
+                            }
+                        },
+                        Action::TogglePaint => {
+                            paint_mode = !paint_mode;
+                            println!("Paint mode: {}", paint_mode);
+                        },
+                        Action::ToggleUi => show_settings_ui = !show_settings_ui,
+                        Action::ToggleMouseGrab => {
+                            mouse_grabbed = !mouse_grabbed;
+                            let window = context.window();
+                            if let Err(e) = window.set_cursor_grab(mouse_grabbed) {
+                                eprintln!("Error setting cursor grab: {}", e);
+                            }
+                            window.set_cursor_visible(!mouse_grabbed);
+                            if mouse_grabbed {
+                                ui_input.release();
+                            } else {
+                                // the mouse is released to use the panels
+                                show_settings_ui = true;
+                            }
+                        },
+                        Action::OverlayDepthUp => {
+                            let level = (octree_overlay.max_level() + 1).min(octree.host.max_depth() - 1);
+                            octree_overlay.set_max_level(level);
+                        },
+                        Action::OverlayDepthDown => {
+                            let level = (octree_overlay.max_level() - 1).max(0);
+                            octree_overlay.set_max_level(level);
+                        },
+                        Action::ToggleFullscreen => {
+                            let window = context.window();
+                            match window.fullscreen() {
+                                Some(_) => window.set_fullscreen(None),
+                                None => window.set_fullscreen(Some(Fullscreen::Borderless(window.current_monitor()))),
+                            }
+                        },
+                        Action::PlayPath | Action::CapturePath => {
+                            if path_playback.is_some() {
+                                path_playback = None;
+                            } else if camera_path.keyframes.len() > 1 {
+                                let capture = if *action == Action::CapturePath {
+                                    match FrameCapture::new(PathBuf::from("captures")) {
+                                        Ok(c) => Some(c),
+                                        Err(e) => {
+                                            eprintln!("{}", e);
+                                            None
+                                        }
+                                    }
+                                } else {
+                                    None
+                                };
+                                path_playback = Some((0.0, capture));
+                                last_keyframe_instant = None;
+                            }
+                        },
+                        _ => { }
+                    }
+                }
             }
 
             // Handle mouse movement. delta contains the x and y movement of the mouse since last frame in pixels
-            if let Ok(mut delta) = mouse_delta.lock() {
+            if mouse_grabbed {
+                let delta = input_state.mouse_delta();
                 const PRECISION: f32 = 0.0001;
-                if delta.1.abs() > PRECISION {
-                    let amount =  chronos.delta_time() as f32 * -delta.1;
-                    camera.turn_pitch(&mut raytrace_program.program, amount);
+                let moved = delta.0.abs() > PRECISION || delta.1.abs() > PRECISION;
+                match camera.mode() {
+                    CameraMode::Free | CameraMode::Walk => {
+                        if delta.1.abs() > PRECISION {
+                            let amount =  chronos.delta_time() as f32 * -delta.1;
+                            camera.turn_pitch(&mut raytrace_program.program, amount);
+                        }
+                        if delta.0.abs() > PRECISION {
+                            let amount = chronos.delta_time() as f32 * -delta.0;
+                            camera.turn_yaw(&mut raytrace_program.program, amount);
+                        } 
+                    },
+                    // left drag orbits, middle drag pans
+                    CameraMode::Orbit { .. } => if moved {
+                        if input_state.held().contains(&Action::OrbitDrag) {
+                            let dt = chronos.delta_time() as f32;
+                            camera.orbit(&mut raytrace_program.program, dt * -delta.0, dt * -delta.1);
+                        } else if input_state.held().contains(&Action::PanDrag) {
+                            camera.orbit_pan(&mut raytrace_program.program, delta.0, delta.1);
+                        }
+                    },
                 }
-                if delta.0.abs() > PRECISION {
-                    let amount = chronos.delta_time() as f32 * -delta.0;
-                    camera.turn_yaw(&mut raytrace_program.program, amount);
-                } 
-                *delta = (0.0, 0.0);
             }
 
-            if last_click_count >= click_cooldown {
-                if let Ok(device_event) = arc_left_mouse.lock() {
-                    let event = *device_event;
-                    match event {
-                        ClickEvent::Left | ClickEvent::Right => {
-                            let mut spawn_point = camera.look_at_world_point(octree.block_distance() * 4.0);
-                            if octree.point_inside(&spawn_point) {
-                                last_click_count = 0.0;
-                                spawn_point = spawn_point - octree.min_point();
-                                // move spawn_point into a unit square
-                                spawn_point /= octree.scale();
-                                delta[0] = spawn_point.x.abs();
-                                delta[1] = spawn_point.y.abs(); 
-                                delta[1] = spawn_point.y.abs(); 
-                                delta[1] = spawn_point.y.abs(); 
-                                delta[2] = spawn_point.z.abs();
-                                delta[3] = 2.0 * (event as i32 as f32);
-                                delta[4] = active_voxel as f32;
-                                octree.update_vbo(&delta, 5, &octree_update_program);
-                            }
-                        }
-                        ClickEvent::None => (),
+            // Handle mouse wheel zoom
+            let scroll = input_state.scroll_delta();
+            if mouse_grabbed && scroll != 0.0 {
+                match camera.mode() {
+                    CameraMode::Free | CameraMode::Walk => camera.zoom(&mut raytrace_program.program, scroll),
+                    CameraMode::Orbit { .. } => camera.orbit_zoom(&mut raytrace_program.program, scroll),
+                }
+            }
+
+            if turntable {
+                camera.turntable(&mut raytrace_program.program, chronos.delta_time() as f32);
+            }
+
+            // mouse buttons are used for orbiting in orbit mode, and for the ui while the mouse is released
+            let editing_enabled = mouse_grabbed && !matches!(camera.mode(), CameraMode::Orbit { .. });
+            let edit_type = |action: &Action| match action {
+                Action::PlaceVoxel => Some(LEAF),
+                Action::RemoveVoxel => Some(EMPTY),
+                _ => None,
+            };
+            // every click is applied, holding the button keeps editing after a cooldown
+            let mut edits: Vec<u32> = input_state.pressed().iter().filter_map(edit_type).collect();
+            if edits.is_empty() && last_click_count >= click_cooldown {
+                edits.extend(input_state.held().iter().filter_map(edit_type).take(1));
+            }
+            if editing_enabled && paint_mode && !edits.is_empty() {
+                last_click_count = 0.0;
+                if let Some((node, _)) = octree_overlay::crosshair_hit(&octree, &camera) {
+                    if edits.contains(&LEAF) {
+                        octree.paint_leaf(&node, active_voxel);
+                    } else if material_library.check_index(node.value).is_ok() {
+                        active_voxel = node.value;
+                        material_editor.select(node.value);
                     }
-                } 
+                }
+            } else if editing_enabled && !edits.is_empty() {
+                let mut spawn_point = camera.look_at_world_point(octree.block_distance() * 4.0);
+                if octree.point_inside(&spawn_point) {
+                    last_click_count = 0.0;
+                    spawn_point = spawn_point - octree.min_point();
+                    // move spawn_point into a unit square
+                    spawn_point /= octree.scale();
+                    for (node, node_type) in delta.chunks_exact_mut(5).zip(edits.iter()) {
+                        node[0] = spawn_point.x.abs();
+                        node[1] = spawn_point.y.abs();
+                        node[2] = spawn_point.z.abs();
+                        node[3] = *node_type as f32;
+                        node[4] = active_voxel as f32;
+                    }
+                    let len = (edits.len() * 5).min(delta.len());
+                    gpu_timer.begin(GpuPass::OctreeUpdate);
+                    octree.update_vbo(&delta, len, &octree_update_program);
+                    gpu_timer.end(GpuPass::OctreeUpdate);
+                    octree_overlay.invalidate();
+                }
             } 
             last_click_count += chronos.delta_time();
+            input_state.end_frame();
             
             
+            // Camera path playback overrides any user movement
+            if let Some((time, _)) = &path_playback {
+                if let Some(pose) = camera_path.sample(*time) {
+                    camera.set_pose(&mut raytrace_program.program, &pose);
+                }
+            }
+            if let Some(pose) = benchmark.as_ref().and_then(|b| b.pose()) {
+                camera.set_pose(&mut raytrace_program.program, &pose);
+            }
+
+            // trade resolution and samples for frame rate
+            let dynamic_settings = camera.settings.dynamic_resolution;
+            let max_scale = camera.settings.render_scale;
+            let max_samples = camera.settings.samples_per_pixel;
+            if dynamic_settings.enabled && dynamic_resolution.update(&dynamic_settings, max_scale, max_samples, chronos.delta_time() as f32) {
+                camera.set_samples_per_pixel(&mut raytrace_program.program, dynamic_resolution.samples());
+                if dynamic_resolution.scale() != camera.render_scale() {
+                    if let Err(e) = camera.set_render_scale(&mut raytrace_program.program, dynamic_resolution.scale()) {
+                        eprintln!("failed to resize render texture: {}", e);
+                    }
+                }
+            }
+
+            if let Err(e) = temporal.prepare(&mut raytrace_program.program, &camera) {
+                eprintln!("failed to prepare temporal accumulation: {}", e);
+            }
+
             octree.vao.bind();
+            let render_size = (camera.render_texture.width(), camera.render_texture.height(), camera.render_texture.depth());
+            gpu_timer.begin(GpuPass::Raytrace);
             raytrace_program.dispatch_compute(render_size.0 + 1, render_size.1 + 1, render_size.2);
+            gpu_timer.end(GpuPass::Raytrace);
             VertexArrayObject::unbind();
+            temporal.finish(&camera);
+
+            if let Some((time, capture)) = &mut path_playback {
+                // captured playback uses a fixed timestep so that the image sequence is reproducible
+                match capture {
+                    Some(c) => {
+                        if let Err(e) = c.capture(&camera.render_texture) {
+                            eprintln!("{}", e);
+                        }
+                        *time += 1.0 / camera_path.capture_fps;
+                    },
+                    None => *time += chronos.delta_time() as f32,
+                }
+
+                if *time > camera_path.duration() {
+                    if let Some(c) = capture {
+                        println!("Captured {} frames", c.frame());
+                    }
+                    path_playback = None;
+                }
+            }
 
+            gpu_timer.begin(GpuPass::Blit);
             quad_program.bind();
             quad_vao.bind();
             quad_indices.bind();
@@ -596,21 +1045,94 @@ fn main() {
             quad_indices.unbind();
             VertexArrayObject::unbind();
             Program::unbind();
+            gpu_timer.end(GpuPass::Blit);
+
+            let show_ui = show_profiler || show_octree_overlay || show_material_editor || paint_mode || show_settings_ui;
+            if show_ui {
+                gpu_timer.begin(GpuPass::Overlay);
+            }
+
+            // leaf in the center of the screen, the octree is in unit space on the host
+            let crosshair_node = if show_octree_overlay {
+                let hit = octree_overlay::crosshair_hit(&octree, &camera);
+                octree_overlay.draw(&octree, &camera, hit.as_ref().map(|(node, _)| node));
+                hit.map(|(node, point)| (node, material_library.surface_albedo(node.value, point)))
+            } else {
+                None
+            };
+
+            if show_ui {
+                let pixels_per_point = context.window().scale_factor() as f32;
+                let raw_input = ui_input.raw_input(screen_size, pixels_per_point, start_instant.elapsed().as_secs_f64());
+                let previous_voxel = active_voxel;
+                let mut settings = camera.settings.clone();
+                let mut settings_changed = false;
+                let output = ui_context.run(raw_input, |ctx| {
+                    if show_profiler {
+                        profiler.show(ctx);
+                    }
+                    if show_octree_overlay {
+                        octree_overlay::show_node_info(ctx, crosshair_node.as_ref().map(|(node, _)| node), crosshair_node.and_then(|(_, albedo)| albedo), octree_overlay.max_level());
+                    } else if show_material_editor || paint_mode {
+                        octree_overlay::show_crosshair(ctx);
+                    }
+                    if show_material_editor {
+                        material_editor.show(ctx, &material_library, paint_mode);
+                    }
+                    if show_settings_ui {
+                        ui_overlay::show_palette(ctx, &material_library, &mut active_voxel);
+                        settings_changed |= ui_overlay::show_camera_settings(ctx, &mut settings);
+                        ui_overlay::show_stats(ctx, profiler.fps(), &octree);
+                    }
+                });
+                if active_voxel != previous_voxel {
+                    material_editor.select(active_voxel);
+                }
+                if settings_changed {
+                    camera.apply_settings(&mut raytrace_program.program, settings);
+                    dynamic_resolution = DynamicResolution::new(camera.render_scale(), camera.settings.samples_per_pixel);
+                    temporal.invalidate();
+                }
+                let primitives = ui_context.tessellate(output.shapes, output.pixels_per_point);
+                ui_painter.paint(screen_size, output.pixels_per_point, &primitives, &output.textures_delta);
+                gpu_timer.end(GpuPass::Overlay);
+            } else {
+                ui_input.clear();
+            }
+
+            let gpu_timings = gpu_timer.end_frame();
+            profiler.record_frame(chronos.delta_time() as f32 * 1000.0, &gpu_timings);
 
             context.swap_buffers().unwrap();
+
+            if let Some(b) = &mut benchmark {
+                b.record_frame(frame_start.elapsed().as_secs_f32() * 1000.0, &gpu_timings);
+                if b.finished() {
+                    b.record_gpu_timings(&gpu_timer.finish());
+                    match b.write_report(Path::new("benchmarks"), &octree) {
+                        Ok(path) => println!("Wrote benchmark report to {:?}", path),
+                        Err(e) => eprintln!("{}", e),
+                    }
+                    return;
+                }
+            }
         }
     }); 
 
     // Keep track of the health of the rendering thread
     let render_thread_healthy = Arc::new(RwLock::new(true));
     let render_thread_watchdog = Arc::clone(&render_thread_healthy);
+    let event_loop_proxy = el.create_proxy();
     thread::spawn(move || {
-        if !render_thread.join().is_ok() {
-            if let Ok(mut health) = render_thread_watchdog.write() {
+        let result = render_thread.join();
+        if let Ok(mut health) = render_thread_watchdog.write() {
+            if result.is_err() {
                 println!("Render thread panicked!");
-                *health = false;
             }
+            *health = false;
         }
+        // wake up the event loop so that it can exit
+        let _ = event_loop_proxy.send_event(());
     });
 
     // Start the event loop -- This is where window events get handled
@@ -626,72 +1148,65 @@ fn main() {
         }
         
 
-        if window_focus {
-            match event {
-                Event::WindowEvent { event: WindowEvent::Focused(f), .. } => {
-                    window_focus = f;
+        // sending fails when the render thread has stopped, in which case the events are not needed
+        match event {
+            Event::WindowEvent { event: WindowEvent::Focused(f), .. } => {
+                window_focus = f;
+                if !f {
+                    let _ = event_sender.send(AppEvent::FocusLost);
                 }
-                Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
-                    *control_flow = ControlFlow::Exit;
-                },
-                // Keep track of currently pressed keys to send to the rendering thread
-                Event::WindowEvent { event: WindowEvent::KeyboardInput {
-                    input: KeyboardInput { state: key_state, virtual_keycode: Some(keycode), .. }, .. }, .. } => {
-    
-                    if let Ok(mut keys) = arc_pressed_keys.lock() {
-                        match key_state {
-                            Released => {
-                                if keys.contains(&keycode) {
-                                    let i = keys.iter().position(|&k| k == keycode).unwrap();
-                                    keys.remove(i);
-                                }
-                            },
-                            Pressed => {
-                                if !keys.contains(&keycode) {
-                                    keys.push(keycode);
-                                }
-                            }
-                        }
-                    }
-    
-                    // Handle escape separately
-                    match keycode {
-                        Escape => {
-                            *control_flow = ControlFlow::Exit;
-                        },
-                        _ => { }
-                    }
-                },
-               
-                Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
-                    // Accumulate mouse movement
-                    if let Ok(mut position) = arc_mouse_delta.lock() {
-                        *position = (position.0 + delta.0 as f32, position.1 + delta.1 as f32);
-                    }
-                },
-                Event::DeviceEvent { event: DeviceEvent::Button { button, state }, .. } => {
-                    let mut event = ClickEvent::None;
-                    if state == ElementState::Pressed {
-                        if button == 1 {
-                            event = ClickEvent::Left;
-                        } else if button == 3 {
-                            event = ClickEvent::Right;
-                        }
-                    }
+            }
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                let _ = event_sender.send(AppEvent::Resized(size.width, size.height));
+            },
+            Event::WindowEvent { event: WindowEvent::ScaleFactorChanged { new_inner_size, .. }, .. } => {
+                let _ = event_sender.send(AppEvent::Resized(new_inner_size.width, new_inner_size.height));
+            },
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                // the render thread stores the camera pose before stopping
+                let _ = event_sender.send(AppEvent::Exit);
+            },
+            // input is only used while the window has focus, the events above are always needed
+            _ if !window_focus => { },
+            // Send key presses and releases to the rendering thread, repeats are sent as presses
+            Event::WindowEvent { event: WindowEvent::KeyboardInput {
+                input: KeyboardInput { state: key_state, virtual_keycode: Some(keycode), .. }, .. }, .. } => {
 
-                    if let Ok(mut device_event) = arc_left_mouse_events.lock() {
-                        *device_event = event;
-                    }
+                let _ = match key_state {
+                    Released => event_sender.send(AppEvent::KeyReleased(keycode)),
+                    Pressed => event_sender.send(AppEvent::KeyPressed(keycode)),
+                };
+
+                // Handle escape separately
+                match keycode {
+                    Escape => {
+                        let _ = event_sender.send(AppEvent::Exit);
+                    },
+                    _ => { }
                 }
-                _ => { }
-            }
-        } else { // window not in focus
-            match event {
-                Event::WindowEvent { event: WindowEvent::Focused(f), .. } => {
-                    window_focus = f;
-                },
-                _ => { }
+            },
+           
+            Event::WindowEvent { event: WindowEvent::CursorMoved { position, .. }, .. } => {
+                let _ = event_sender.send(AppEvent::CursorMoved(position.x as f32, position.y as f32));
+            },
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
+                let _ = event_sender.send(AppEvent::MouseMoved(delta.0 as f32, delta.1 as f32));
+            },
+            Event::WindowEvent { event: WindowEvent::MouseWheel { delta, .. }, .. } => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    // roughly one line per 20 pixels
+                    MouseScrollDelta::PixelDelta(p) => p.y as f32 / 20.0,
+                };
+                let _ = event_sender.send(AppEvent::Scrolled(steps));
+            },
+            Event::DeviceEvent { event: DeviceEvent::Button { button, state }, .. } => {
+                let _ = match state {
+                    Pressed => event_sender.send(AppEvent::ButtonPressed(button)),
+                    Released => event_sender.send(AppEvent::ButtonReleased(button)),
+                };
             }
+            _ => { }
         }
     });
-}
\ No newline at end of file
+}
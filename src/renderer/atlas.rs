@@ -0,0 +1,97 @@
+use serde::{Serialize, Deserialize};
+
+use super::{materials::MaterialLibrary, program::Program, texture::Texture};
+use crate::resources::Resources;
+
+// texture unit of the atlas sampler in raytracer.comp, 0 - 4 are the render and temporal textures and 5 the ui
+const ATLAS_TEXTURE_UNIT: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum AtlasFilter {
+    #[default]
+    Nearest,
+    Linear,
+}
+
+/// Image with square tiles that voxel faces are textured with
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AtlasDesc {
+    // resource path of the image
+    pub image: String,
+    // width and height of a tile in pixels
+    pub tile_size: u32,
+    #[serde(default)]
+    pub filter: AtlasFilter,
+}
+
+/// Atlas tiles of the faces of a voxel, counted row by row from the top left tile
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct FaceTiles {
+    pub top: u32,
+    pub side: u32,
+    pub bottom: u32,
+}
+
+/// The atlas of a material library uploaded as a texture
+pub struct TextureAtlas {
+    texture: Texture,
+    columns: u32,
+    rows: u32,
+}
+
+impl TextureAtlas {
+    /// Load the atlas of the library and check that the tiles of every material exist,
+    /// none if the library has no atlas
+    pub fn from_library(res: &Resources, library: &MaterialLibrary) -> Result<Option<TextureAtlas>, String> {
+        let desc = match &library.atlas {
+            Some(desc) => desc,
+            None => {
+                if let Some(material) = library.materials.iter().find(|m| m.texture.is_some()) {
+                    return Err(format!("'{}' has a texture, but there is no atlas", material.name));
+                }
+                return Ok(None);
+            },
+        };
+
+        let image = res.load_image(&desc.image)
+            .map_err(|e| format!("Error loading atlas {}: {}", desc.image, e))?
+            .to_rgba();
+        let (width, height) = image.dimensions();
+        if desc.tile_size == 0 || width % desc.tile_size != 0 || height % desc.tile_size != 0 {
+            return Err(format!("Atlas {} of {}x{} can not be split into tiles of {}", desc.image, width, height, desc.tile_size));
+        }
+        let columns = width / desc.tile_size;
+        let rows = height / desc.tile_size;
+
+        for material in library.materials.iter() {
+            if let Some(tiles) = material.texture {
+                if [tiles.top, tiles.side, tiles.bottom].iter().any(|tile| *tile >= columns * rows) {
+                    return Err(format!("'{}' uses tiles {:?}, the atlas has {}", material.name, tiles, columns * rows));
+                }
+            }
+        }
+
+        let filter = match desc.filter {
+            AtlasFilter::Nearest => gl::NEAREST,
+            AtlasFilter::Linear => gl::LINEAR,
+        };
+        let texture = Texture::new_sampled_2d(gl::TEXTURE0 + ATLAS_TEXTURE_UNIT, width as i32, height as i32, &image.into_raw(), filter)
+            .map_err(|e| format!("Failed to create atlas texture: {}", e))?;
+
+        Ok(Some(TextureAtlas { texture, columns, rows }))
+    }
+
+    /// Bind the atlas and set its uniforms, or disable texturing if there is no atlas
+    pub fn apply(atlas: Option<&TextureAtlas>, program: &mut Program) -> Result<(), String> {
+        let (columns, rows) = match atlas {
+            Some(atlas) => {
+                atlas.texture.bind();
+                (atlas.columns as i32, atlas.rows as i32)
+            },
+            None => (0, 0),
+        };
+        program.set_i32("atlas", ATLAS_TEXTURE_UNIT as i32).map_err(|e| format!("{}", e))?;
+        program.set_i32("atlas_columns", columns).map_err(|e| format!("{}", e))?;
+        program.set_i32("atlas_rows", rows).map_err(|e| format!("{}", e))
+    }
+}
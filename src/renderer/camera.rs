@@ -1,11 +1,33 @@
-use cgmath::{InnerSpace, Quaternion, Rotation, Vector3};
+use cgmath::{InnerSpace, Quaternion, Rad, Rotation, Rotation3, Vector3};
 
-use crate::renderer::texture::Texture;
+use std::collections::HashMap;
+
+use crate::{renderer::{fog::FogSettings, shading::ShadingSettings, temporal::TemporalSettings, texture::Texture}, resources::Resources, utility::{dynamic_resolution::DynamicResolutionSettings, walk::WalkSettings}};
 
 use super::{InitializeErr, program::Program};
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+// Must match the PROJ_* constants in raytracer.comp
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub enum Projection {
+    #[default]
+    Perspective = 0,
+    Orthographic,
+    Equirectangular,
+    Fisheye,
+}
+
+// Must match the UPSCALE_* constants in quad.frag
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub enum Upscale {
+    Nearest = 0,
+    #[default]
+    Bilinear,
+    // FSR1 style edge adaptive filter
+    EdgeAdaptive,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct CameraSettings {
     // TODO: these are only i32 because it is easier to send to GPU
     pub samples_per_pixel: i32,
@@ -13,7 +35,223 @@ pub struct CameraSettings {
     pub turn_rate: f32,
     pub normal_speed: f32,
     pub sprint_speed: f32,
+    #[serde(default)]
+    pub projection: Projection,
+    // scale of the orthographic view volume relative to the perspective viewport
+    #[serde(default = "default_ortho_scale")]
+    pub ortho_scale: f32,
+    // field of view of the fisheye image circle in degrees
+    #[serde(default = "default_fisheye_fov")]
+    pub fisheye_fov: f32,
+    // pitch is clamped to [-max_pitch, max_pitch] degrees to avoid flipping over the top
+    #[serde(default = "default_max_pitch")]
+    pub max_pitch: f32,
+    #[serde(default)]
+    pub allow_roll: bool,
+    // vertical fov change in degrees for each zoom step
+    #[serde(default = "default_zoom_rate")]
+    pub zoom_rate: f32,
+    #[serde(default = "default_min_fov")]
+    pub min_fov: f32,
+    #[serde(default = "default_max_fov")]
+    pub max_fov: f32,
+    // world up direction, yaw rotates around this axis
+    #[serde(default = "default_up")]
+    pub up: Vector3<f32>,
+    // fraction of the orbit distance moved for each zoom step in orbit mode
+    #[serde(default = "default_orbit_zoom_rate")]
+    pub orbit_zoom_rate: f32,
+    // automatic orbit rotation in degrees per second
+    #[serde(default = "default_turntable_speed")]
+    pub turntable_speed: f32,
+    #[serde(default)]
+    pub walk: WalkSettings,
+    // render resolution relative to the window, the render texture is upscaled to the window
+    #[serde(default = "default_render_scale")]
+    pub render_scale: f32,
+    #[serde(default)]
+    pub upscale: Upscale,
+    #[serde(default)]
+    pub dynamic_resolution: DynamicResolutionSettings,
+    #[serde(default)]
+    pub temporal: TemporalSettings,
+    #[serde(default)]
+    pub fog: FogSettings,
+    #[serde(default)]
+    pub shading: ShadingSettings,
+}
+
+fn default_ortho_scale() -> f32 {
+    0.5
+}
+
+fn default_fisheye_fov() -> f32 {
+    180.0
+}
+
+fn default_max_pitch() -> f32 {
+    89.0
+}
+
+fn default_zoom_rate() -> f32 {
+    2.0
+}
+
+fn default_min_fov() -> f32 {
+    10.0
+}
+
+fn default_max_fov() -> f32 {
+    150.0
+}
+
+fn default_up() -> Vector3<f32> {
+    Vector3::unit_y()
+}
+
+fn default_orbit_zoom_rate() -> f32 {
+    0.1
+}
+
+fn default_turntable_speed() -> f32 {
+    20.0
+}
+
+fn default_render_scale() -> f32 {
+    1.0
+}
+
+impl CameraSettings {
+    /// Load and validate the settings
+    pub fn from_resources(res: &Resources, name: &str) -> Result<CameraSettings, String> {
+        let bytes = res.load_buffer(name)
+            .map_err(|e| format!("Error loading resource {}: {:?}", name, e))?;
+        let settings: CameraSettings = ron::de::from_bytes(&bytes[0..])
+            .map_err(|e| format!("Error parsing {}: {}", name, e))?;
+        settings.validate().map_err(|e| format!("Invalid {}: {}", name, e))?;
+        Ok(settings)
+    }
+
+    /// Check the limits that the camera clamps to, clamping panics on an empty range, and that up is a direction
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0 < self.min_fov && self.min_fov <= self.max_fov && self.max_fov < 180.0) {
+            return Err(format!("min_fov {} and max_fov {} must be in 0 < min_fov <= max_fov < 180", self.min_fov, self.max_fov));
+        }
+        if !(0.0..=90.0).contains(&self.max_pitch) {
+            return Err(format!("max_pitch {} must be in 0 - 90", self.max_pitch));
+        }
+        if !(self.orbit_zoom_rate > 0.0 && self.orbit_zoom_rate < 1.0) {
+            return Err(format!("orbit_zoom_rate {} must be in 0 - 1, not including either", self.orbit_zoom_rate));
+        }
+        if !(self.up.x.is_finite() && self.up.y.is_finite() && self.up.z.is_finite()) || self.up.magnitude() < 1e-6 {
+            return Err(format!("up {:?} must be a finite, non zero vector", self.up));
+        }
+        Ok(())
+    }
+}
+
+// walk mode falls along -y
+fn walk_up(settings: &CameraSettings) -> bool {
+    settings.up.normalize().y > 0.999
+}
+
+// size of the render texture for a window size
+fn render_size(window_size: (i32, i32), render_scale: f32) -> (i32, i32) {
+    let scale = render_scale.clamp(0.05, 4.0);
+    (
+        ((window_size.0 as f32 * scale) as i32).max(1),
+        ((window_size.1 as f32 * scale) as i32).max(1),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraMode {
+    // free flying camera
+    Free,
+    // camera rotates around target at distance
+    Orbit { target: Vector3<f32>, distance: f32 },
+    // camera follows a body that walks on the voxels
+    Walk,
+}
+
+// Size of the viewport one unit in front of the camera
+fn viewport_size(vertical_fov: f32, viewport_scale: f32, aspect_ratio: f32) -> (f32, f32) {
+    let theta = vertical_fov.to_radians();
+    let h = (theta / 2.0).tan();
+    let viewport_height = viewport_scale * h;
+    (aspect_ratio * viewport_height, viewport_height)
+}
+/// A viewpoint that can be stored and restored, angles are in degrees
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct CameraPose {
+    pub origin: Vector3<f32>,
+    pub pitch: f32,
+    pub yaw: f32,
+    #[serde(default)]
+    pub roll: f32,
+    pub vertical_fov: f32,
+}
+
+impl CameraPose {
+    /// Orientation relative to the world up frame
+    pub fn orientation(&self) -> Quaternion<f32> {
+        let yaw = Quaternion::from_angle_y(Rad(self.yaw.to_radians()));
+        let pitch = Quaternion::from_angle_x(Rad(self.pitch.to_radians()));
+        let roll = Quaternion::from_angle_z(Rad(self.roll.to_radians()));
+        yaw * pitch * roll
+    }
+
+    /// Set pitch, yaw and roll from an orientation relative to the world up frame
+    pub fn set_orientation(&mut self, orientation: Quaternion<f32>) {
+        let view = orientation.rotate_vector(-Vector3::unit_z());
+        let pitch = view.y.clamp(-1.0, 1.0).asin();
+        let yaw = (-view.x).atan2(-view.z);
+
+        // whatever rotation is left after yaw and pitch is the roll
+        let yaw_pitch = Quaternion::from_angle_y(Rad(yaw)) * Quaternion::from_angle_x(Rad(pitch));
+        let mut roll = yaw_pitch.invert() * orientation;
+        // q and -q is the same rotation, pick the one that gives roll in [-180, 180]
+        if roll.s < 0.0 {
+            roll = -roll;
+        }
+
+        self.pitch = pitch.to_degrees();
+        self.yaw = yaw.to_degrees();
+        self.roll = (2.0 * roll.v.z.atan2(roll.s)).to_degrees();
+    }
+}
+
+/// Poses persisted between runs: the last pose on exit and the number row bookmarks
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct CameraPoses {
+    pub last: Option<CameraPose>,
+    #[serde(default)]
+    pub bookmarks: HashMap<u8, CameraPose>,
 }
+
+impl CameraPoses {
+    /// Load poses from a ron resource, a missing or invalid file gives no poses
+    pub fn from_resources(res: &Resources, name: &str) -> CameraPoses {
+        match res.load_buffer(name) {
+            Ok(bytes) => ron::de::from_bytes(&bytes[0..]).unwrap_or_else(|e| {
+                eprintln!("Failed to parse {}: {}", name, e);
+                CameraPoses::default()
+            }),
+            Err(_) => CameraPoses::default(),
+        }
+    }
+
+    pub fn save(&self, res: &Resources, name: &str) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| format!("{}", e))
+            .and_then(|s| res.write_buffer(name, s.as_bytes()).map_err(|e| format!("{:?}", e)));
+
+        if let Err(e) = result {
+            eprintln!("Failed to save {}: {}", name, e);
+        }
+    }
+}
+
 // TODO: camera should have a say when it comes to viewport and program window
 // TODO: some of the cameras variables can be remove as they are only used when 
 //       they are calculated (horizontal, vertical, lower_left_corner ...)
@@ -25,8 +263,17 @@ pub struct Camera {
 
     pub lower_left_corner: Vector3::<f32>,
     pub origin: Vector3::<f32>,
-    pitch: Quaternion<f32>,
-    yaw: Quaternion<f32>,
+    // euler angles in radians, applied as yaw, then pitch, then roll
+    pitch: f32,
+    yaw: f32,
+    roll: f32,
+    vertical_fov: f32,
+    viewport_scale: f32,
+    aspect_ratio: f32,
+    // size of the window the render texture is displayed in
+    window_size: (i32, i32),
+    // current render scale, can differ from the settings when dynamic resolution is used
+    render_scale: f32,
     
     pub image_width: i32,
     pub image_height: i32,
@@ -34,41 +281,211 @@ pub struct Camera {
     // TODO: rename configurable
     pub settings: CameraSettings,
     pub movement_speed: f32,
+    mode: CameraMode,
 }
 
 impl Camera {
     pub fn translate(&mut self, program: &mut Program, by: &Vector3::<f32>, deltatime: f64) {
-        self.origin += self.orientation().rotate_vector(*by * deltatime as f32 * self.movement_speed);
+        let offset = self.orientation().rotate_vector(*by * deltatime as f32 * self.movement_speed);
+        self.origin += offset;
+        // moving in orbit mode drags the target along
+        if let CameraMode::Orbit { target, .. } = &mut self.mode {
+            *target += offset;
+        }
+        self.propagate_changes(program);
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Orbit around target at its current distance, turning the camera to face it
+    pub fn enter_orbit(&mut self, program: &mut Program, target: Vector3<f32>) {
+        const MIN_DISTANCE: f32 = 0.001;
+        self.mode = CameraMode::Free;
+        self.look_at(program, target);
+        self.mode = CameraMode::Orbit {
+            target,
+            distance: (target - self.origin).magnitude().max(MIN_DISTANCE),
+        };
+        self.propagate_changes(program);
+    }
+
+    /// Return to free flying, the current view is kept
+    pub fn enter_free(&mut self) {
+        self.mode = CameraMode::Free;
+    }
+
+    /// Walk on the voxels, the origin is controlled by the walk body. The body falls along -y, so up must be +y
+    pub fn enter_walk(&mut self) -> Result<(), String> {
+        if !walk_up(&self.settings) {
+            return Err(format!("Walk mode needs up to be +y, not {:?}", self.settings.up));
+        }
+        self.mode = CameraMode::Walk;
+        Ok(())
+    }
+
+    pub fn set_origin(&mut self, program: &mut Program, origin: Vector3<f32>) {
+        self.origin = origin;
+        self.propagate_changes(program);
+    }
+
+    /// Rotate a direction by yaw only, so that walking ignores where the camera is looking vertically
+    pub fn walk_direction(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        Quaternion::from_angle_y(Rad(self.yaw)).rotate_vector(*direction)
+    }
+
+    /// Rotate around the orbit target, angles are scaled by turn rate like turn_pitch and turn_yaw
+    pub fn orbit(&mut self, program: &mut Program, yaw_angle: f32, pitch_angle: f32) {
+        if !matches!(self.mode, CameraMode::Orbit { .. }) {
+            return;
+        }
+        let max_pitch = self.settings.max_pitch.to_radians();
+        self.yaw = (self.yaw + 2.0 * yaw_angle * self.settings.turn_rate) % std::f32::consts::TAU;
+        self.pitch = (self.pitch + 2.0 * pitch_angle * self.settings.turn_rate).clamp(-max_pitch, max_pitch);
+        self.propagate_changes(program);
+    }
+
+    /// Move closer to the orbit target, positive steps move closer
+    pub fn orbit_zoom(&mut self, program: &mut Program, steps: f32) {
+        if let CameraMode::Orbit { distance, .. } = &mut self.mode {
+            *distance *= (1.0 - self.settings.orbit_zoom_rate).powf(steps);
+        }
+        self.propagate_changes(program);
+    }
+
+    /// Move the orbit target in the view plane by a mouse movement in pixels
+    pub fn orbit_pan(&mut self, program: &mut Program, dx: f32, dy: f32) {
+        let orientation = self.orientation();
+        let right = orientation.rotate_vector(Vector3::unit_x());
+        let up = orientation.rotate_vector(Vector3::unit_y());
+        let viewport_height = self.viewport_height;
+        let image_height = self.image_height as f32;
+        if let CameraMode::Orbit { target, distance } = &mut self.mode {
+            // one pixel of movement moves the target one pixel on screen
+            let pixel_size = *distance * viewport_height / image_height;
+            *target += (right * -dx + up * dy) * pixel_size;
+        }
+        self.propagate_changes(program);
+    }
+
+    /// Automatic rotation around the orbit target
+    pub fn turntable(&mut self, program: &mut Program, delta_time: f32) {
+        if !matches!(self.mode, CameraMode::Orbit { .. }) {
+            return;
+        }
+        self.yaw = (self.yaw + self.settings.turntable_speed.to_radians() * delta_time) % std::f32::consts::TAU;
         self.propagate_changes(program);
     }
+
+    fn view_direction(&self) -> Vector3<f32> {
+        self.orientation().rotate_vector(-Vector3::unit_z()).normalize()
+    }
      
     // turn in x axis
     pub fn turn_pitch(&mut self, program: &mut Program, angle: f32) {
-        // Axis angle to quaternion: https://www.euclideanspace.com/maths/geometry/rotations/conversions/angleToQuaternion/index.htm
-        let h_angle = angle * self.settings.turn_rate;
-        let i = h_angle.sin();
-        let w = h_angle.cos();
-        self.pitch = self.pitch * Quaternion::new(w, i, 0.0, 0.0).normalize();
+        // turn_rate is applied to the half angle, as it was when turning was done directly with quaternions
+        let max_pitch = self.settings.max_pitch.to_radians();
+        self.pitch = (self.pitch + 2.0 * angle * self.settings.turn_rate).clamp(-max_pitch, max_pitch);
         self.propagate_changes(program);
     }
 
     // turn in y axis
     pub fn turn_yaw(&mut self, program: &mut Program, angle: f32) {
-        let h_angle = angle * self.settings.turn_rate;
-        let j = h_angle.sin();
-        let w = h_angle.cos();
-        self.yaw = self.yaw * Quaternion::new(w, 0.0, j, 0.0).normalize();
+        self.yaw = (self.yaw + 2.0 * angle * self.settings.turn_rate) % std::f32::consts::TAU;
+        self.propagate_changes(program);
+    }
+
+    // turn in z axis, does nothing unless roll is allowed in settings
+    pub fn turn_roll(&mut self, program: &mut Program, angle: f32) {
+        if !self.settings.allow_roll {
+            return;
+        }
+        self.roll = (self.roll + 2.0 * angle * self.settings.turn_rate) % std::f32::consts::TAU;
+        self.propagate_changes(program);
+    }
+
+    /// Zoom by changing the vertical field of view, positive steps zoom in
+    pub fn zoom(&mut self, program: &mut Program, steps: f32) {
+        let fov = self.vertical_fov - steps * self.settings.zoom_rate;
+        self.set_vertical_fov(program, fov);
+    }
+
+    pub fn set_vertical_fov(&mut self, program: &mut Program, vertical_fov: f32) {
+        self.vertical_fov = vertical_fov.clamp(self.settings.min_fov, self.settings.max_fov);
+        self.propagate_changes(program);
+    }
+
+    #[allow(dead_code)]
+    pub fn vertical_fov(&self) -> f32 {
+        self.vertical_fov
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            origin: self.origin,
+            pitch: self.pitch.to_degrees(),
+            yaw: self.yaw.to_degrees(),
+            roll: self.roll.to_degrees(),
+            vertical_fov: self.vertical_fov,
+        }
+    }
+
+    pub fn set_pose(&mut self, program: &mut Program, pose: &CameraPose) {
+        self.mode = CameraMode::Free;
+        self.origin = pose.origin;
+        self.set_angles(pose);
+        self.set_vertical_fov(program, pose.vertical_fov);
+    }
+
+    // set orientation from pose within the limits of the current settings
+    fn set_angles(&mut self, pose: &CameraPose) {
+        let max_pitch = self.settings.max_pitch.to_radians();
+        self.pitch = pose.pitch.to_radians().clamp(-max_pitch, max_pitch);
+        self.yaw = pose.yaw.to_radians() % std::f32::consts::TAU;
+        self.roll = if self.settings.allow_roll { pose.roll.to_radians() } else { 0.0 };
+    }
+
+    /// Turn the camera so that it faces target. Roll is kept as is
+    pub fn look_at(&mut self, program: &mut Program, target: Vector3<f32>) {
+        let to_target = target - self.origin;
+        if to_target.magnitude2() <= f32::EPSILON {
+            return;
+        }
+
+        // direction relative to the world up frame
+        let local = self.up_frame().invert().rotate_vector(to_target.normalize());
+        let max_pitch = self.settings.max_pitch.to_radians();
+        self.pitch = local.y.clamp(-1.0, 1.0).asin().clamp(-max_pitch, max_pitch);
+        self.yaw = (-local.x).atan2(-local.z);
         self.propagate_changes(program);
     }
 
+    // rotation from the default up (y axis) to the world up
+    fn up_frame(&self) -> Quaternion<f32> {
+        Quaternion::from_arc(Vector3::unit_y(), self.settings.up.normalize(), None)
+    }
+
     fn orientation(&self) -> Quaternion<f32> {
-        return (self.yaw * self.pitch).normalize();
+        let yaw = Quaternion::from_angle_y(Rad(self.yaw));
+        let pitch = Quaternion::from_angle_x(Rad(self.pitch));
+        let roll = Quaternion::from_angle_z(Rad(self.roll));
+        (self.up_frame() * yaw * pitch * roll).normalize()
     }
 
     fn propagate_changes(&mut self, program: &mut Program) {
-        let forward = (self.orientation().rotate_vector(Vector3::unit_z())).normalize();
-        let right = Vector3::unit_y().cross(forward).normalize();
-        let up = forward.cross(right).normalize();
+        if let CameraMode::Orbit { target, distance } = self.mode {
+            self.origin = target - self.view_direction() * distance;
+        }
+
+        let (viewport_width, viewport_height) = viewport_size(self.vertical_fov, self.viewport_scale, self.aspect_ratio);
+        self.viewport_width = viewport_width;
+        self.viewport_height = viewport_height;
+
+        let orientation = self.orientation();
+        let forward = orientation.rotate_vector(Vector3::unit_z()).normalize();
+        let right = orientation.rotate_vector(Vector3::unit_x()).normalize();
+        let up = orientation.rotate_vector(Vector3::unit_y()).normalize();
 
         self.horizontal = right * self.viewport_width;
         self.vertical = up * self.viewport_height;
@@ -93,11 +510,66 @@ impl Camera {
         self.orientation().rotate_vector(-Vector3::unit_z() * distance) + self.origin
     }
 
+    /// Reallocate the render texture for a new window size, the aspect ratio follows the window
+    pub fn resize(&mut self, program: &mut Program, window_width: i32, window_height: i32) -> Result<(), InitializeErr> {
+        self.window_size = (window_width.max(1), window_height.max(1));
+        self.aspect_ratio = self.window_size.0 as f32 / self.window_size.1 as f32;
+
+        let (image_width, image_height) = render_size(self.window_size, self.render_scale);
+        if image_width != self.image_width || image_height != self.image_height {
+            self.render_texture.resize_2d(image_width, image_height)?;
+            self.image_width = image_width;
+            self.image_height = image_height;
+            program.set_i32("camera.image_width", self.image_width).unwrap();
+            program.set_i32("camera.image_height", self.image_height).unwrap();
+        }
+
+        self.propagate_changes(program);
+        Ok(())
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Change the render resolution relative to the window without changing the settings
+    pub fn set_render_scale(&mut self, program: &mut Program, render_scale: f32) -> Result<(), InitializeErr> {
+        self.render_scale = render_scale;
+        let (width, height) = self.window_size;
+        self.resize(program, width, height)
+    }
+
+    /// Change samples per pixel without changing the settings
+    pub fn set_samples_per_pixel(&mut self, program: &mut Program, samples_per_pixel: i32) {
+        program.set_i32("camera.samples_per_pixel", samples_per_pixel).unwrap();
+    }
+
     pub fn apply_settings(&mut self, program: &mut Program, settings: CameraSettings) {
         self.settings = settings;
+        if self.settings.render_scale != self.render_scale {
+            if let Err(e) = self.set_render_scale(program, self.settings.render_scale) {
+                eprintln!("failed to resize render texture: {}", e);
+            }
+        }
+
+        // new limits might not allow the current orientation and fov
+        let max_pitch = self.settings.max_pitch.to_radians();
+        self.pitch = self.pitch.clamp(-max_pitch, max_pitch);
+        if !self.settings.allow_roll {
+            self.roll = 0.0;
+        }
+        self.vertical_fov = self.vertical_fov.clamp(self.settings.min_fov, self.settings.max_fov);
+        if self.mode == CameraMode::Walk && !walk_up(&self.settings) {
+            eprintln!("Walk mode needs up to be +y, flying instead");
+            self.mode = CameraMode::Free;
+        }
+        self.propagate_changes(program);
 
         program.set_i32("camera.samples_per_pixel", self.settings.samples_per_pixel).unwrap();
         program.set_i32("camera.max_bounce", self.settings.max_bounce).unwrap();
+        projection_uniforms(&self.settings, program);
+        self.settings.fog.apply(program).unwrap();
+        self.settings.shading.apply(program).unwrap();
     }
 }
 
@@ -113,9 +585,29 @@ pub struct CameraBuilder {
     turn_rate: Option<f32>,
     normal_speed: Option<f32>,
     sprint_speed: Option<f32>,
+    projection: Option<Projection>,
+    ortho_scale: Option<f32>,
+    fisheye_fov: Option<f32>,
+    max_pitch: Option<f32>,
+    allow_roll: Option<bool>,
+    zoom_rate: Option<f32>,
+    min_fov: Option<f32>,
+    max_fov: Option<f32>,
+    up: Option<Vector3<f32>>,
+    orbit_zoom_rate: Option<f32>,
+    turntable_speed: Option<f32>,
+    walk: Option<WalkSettings>,
+    render_scale: Option<f32>,
+    upscale: Option<Upscale>,
+    dynamic_resolution: Option<DynamicResolutionSettings>,
+    temporal: Option<TemporalSettings>,
+    fog: Option<FogSettings>,
+    shading: Option<ShadingSettings>,
+    pose: Option<CameraPose>,
 }
 
 impl CameraBuilder {
+    // image_width is the width of the window, the render texture is scaled by the render scale
     pub fn new(vertical_fov: f32, image_width: i32) -> CameraBuilder {
         // create the camera builder with defaults
         CameraBuilder {
@@ -129,29 +621,41 @@ impl CameraBuilder {
             turn_rate: None,
             normal_speed: None,
             sprint_speed: None,
+            projection: None,
+            ortho_scale: None,
+            fisheye_fov: None,
+            max_pitch: None,
+            allow_roll: None,
+            zoom_rate: None,
+            min_fov: None,
+            max_fov: None,
+            up: None,
+            orbit_zoom_rate: None,
+            turntable_speed: None,
+            walk: None,
+            render_scale: None,
+            upscale: None,
+            dynamic_resolution: None,
+            temporal: None,
+            fog: None,
+            shading: None,
+            pose: None,
         }
     }
 
     pub fn build(&mut self, program: &mut Program) -> Result<Camera, InitializeErr> {
         let aspect_ratio = self.aspect_ratio.unwrap_or(16.0 / 9.0);
 
-        let theta = self.vertical_fov * std::f32::consts::PI / 180.0;
-        let h = (theta / 2.0).tan();
-        let viewport_height = self.viewport_height.unwrap_or(2.0) * h;
-        let viewport_width = aspect_ratio * viewport_height;
-        
-        let origin = self.origin.unwrap_or(Vector3::new(0.0, 0.0, 0.0));
-        
-        let forward = (Vector3::unit_z()).normalize();
-        let right = Vector3::unit_y().cross(forward).normalize();
-        let up = forward.cross(right).normalize();
-
-        let horizontal: Vector3<f32> = right * viewport_width;
-        let vertical: Vector3<f32> = up * viewport_height;
+        let viewport_scale = self.viewport_height.unwrap_or(2.0);
+        let (viewport_width, viewport_height) = viewport_size(self.vertical_fov, viewport_scale, aspect_ratio);
         
-        let lower_left_corner = origin - horizontal * 0.5 - vertical * 0.5 - forward;
+        let origin = self.pose.map(|p| p.origin)
+            .or(self.origin)
+            .unwrap_or(Vector3::new(0.0, 0.0, 0.0));
 
-        let image_height = (self.image_width as f32 / aspect_ratio) as i32;
+        let window_size = (self.image_width, (self.image_width as f32 / aspect_ratio) as i32);
+        let render_scale = self.render_scale.unwrap_or_else(default_render_scale);
+        let (image_width, image_height) = render_size(window_size, render_scale);
 
         let sample_per_pixel = self.samples_per_pixel.unwrap_or(10);
         let max_bounce = self.max_bounce.unwrap_or(3);
@@ -160,7 +664,7 @@ impl CameraBuilder {
             0, 
             gl::RGBA32F, 
             gl::RGBA, 
-            self.image_width, 
+            image_width, 
             image_height
         )?;
 
@@ -168,29 +672,70 @@ impl CameraBuilder {
         let normal_speed = self.normal_speed.unwrap_or(1.0);
         let sprint_speed = self.sprint_speed.unwrap_or(normal_speed * 2.0);
 
-        let camera = Camera {
-            horizontal,
-            vertical,
+        let mut settings = CameraSettings {
+            samples_per_pixel: sample_per_pixel,
+            max_bounce,
+            turn_rate,
+            normal_speed,
+            sprint_speed,
+            projection: self.projection.unwrap_or_default(),
+            ortho_scale: self.ortho_scale.unwrap_or_else(default_ortho_scale),
+            fisheye_fov: self.fisheye_fov.unwrap_or_else(default_fisheye_fov),
+            max_pitch: self.max_pitch.unwrap_or_else(default_max_pitch),
+            allow_roll: self.allow_roll.unwrap_or(false),
+            zoom_rate: self.zoom_rate.unwrap_or_else(default_zoom_rate),
+            min_fov: self.min_fov.unwrap_or_else(default_min_fov),
+            max_fov: self.max_fov.unwrap_or_else(default_max_fov),
+            up: self.up.unwrap_or_else(default_up),
+            orbit_zoom_rate: self.orbit_zoom_rate.unwrap_or_else(default_orbit_zoom_rate),
+            turntable_speed: self.turntable_speed.unwrap_or_else(default_turntable_speed),
+            walk: self.walk.unwrap_or_default(),
+            render_scale,
+            upscale: self.upscale.unwrap_or_default(),
+            dynamic_resolution: self.dynamic_resolution.unwrap_or_default(),
+            temporal: self.temporal.unwrap_or_default(),
+            fog: self.fog.unwrap_or_default(),
+            shading: self.shading.unwrap_or_default(),
+        };
+        // the limits are used for clamping and up for the camera frame, invalid ones are replaced by the defaults
+        if let Err(e) = settings.validate() {
+            eprintln!("Invalid camera settings: {}", e);
+            settings.max_pitch = default_max_pitch();
+            settings.min_fov = default_min_fov();
+            settings.max_fov = default_max_fov();
+            settings.up = default_up();
+            settings.orbit_zoom_rate = default_orbit_zoom_rate();
+        }
+
+        let mut camera = Camera {
+            horizontal: Vector3::unit_x(),
+            vertical: Vector3::unit_y(),
             viewport_width,
             viewport_height,
-            lower_left_corner,
+            lower_left_corner: origin,
             origin,
-            pitch: Quaternion::new(1.0, 0.0, 0.0, 0.0),
-            yaw: Quaternion::new(1.0, 0.0, 0.0, 0.0),
-            image_width: self.image_width,
+            pitch: 0.0,
+            yaw: 0.0,
+            roll: 0.0,
+            vertical_fov: self.vertical_fov,
+            viewport_scale,
+            aspect_ratio,
+            window_size,
+            render_scale,
+            image_width,
             image_height,
             render_texture,
-            settings: CameraSettings {
-                samples_per_pixel: sample_per_pixel,
-                max_bounce,
-                turn_rate,
-                normal_speed,
-                sprint_speed
-            },
-            movement_speed: normal_speed
+            settings,
+            movement_speed: normal_speed,
+            mode: CameraMode::Free,
         };
 
-        initial_uniforms(&camera, program);
+        if let Some(pose) = self.pose {
+            camera.set_angles(&pose);
+            camera.vertical_fov = pose.vertical_fov.clamp(camera.settings.min_fov, camera.settings.max_fov);
+        }
+
+        initial_uniforms(&mut camera, program);
 
         return Ok(camera);
     }
@@ -234,20 +779,111 @@ impl CameraBuilder {
         self.sprint_speed = Some(sprint_speed);
         return self;
     }
+
+    pub fn with_projection(&mut self, projection: Projection) -> &mut CameraBuilder {
+        self.projection = Some(projection);
+        self
+    }
+
+    pub fn with_ortho_scale(&mut self, ortho_scale: f32) -> &mut CameraBuilder {
+        self.ortho_scale = Some(ortho_scale);
+        self
+    }
+
+    pub fn with_fisheye_fov(&mut self, fisheye_fov: f32) -> &mut CameraBuilder {
+        self.fisheye_fov = Some(fisheye_fov);
+        self
+    }
+
+    pub fn with_max_pitch(&mut self, max_pitch: f32) -> &mut CameraBuilder {
+        self.max_pitch = Some(max_pitch);
+        self
+    }
+
+    pub fn with_roll(&mut self, allow_roll: bool) -> &mut CameraBuilder {
+        self.allow_roll = Some(allow_roll);
+        self
+    }
+
+    pub fn with_zoom(&mut self, zoom_rate: f32, min_fov: f32, max_fov: f32) -> &mut CameraBuilder {
+        self.zoom_rate = Some(zoom_rate);
+        self.min_fov = Some(min_fov);
+        self.max_fov = Some(max_fov);
+        self
+    }
+
+    pub fn with_up(&mut self, up: Vector3<f32>) -> &mut CameraBuilder {
+        self.up = Some(up);
+        self
+    }
+
+    pub fn with_orbit(&mut self, orbit_zoom_rate: f32, turntable_speed: f32) -> &mut CameraBuilder {
+        self.orbit_zoom_rate = Some(orbit_zoom_rate);
+        self.turntable_speed = Some(turntable_speed);
+        self
+    }
+
+    pub fn with_walk(&mut self, walk: WalkSettings) -> &mut CameraBuilder {
+        self.walk = Some(walk);
+        self
+    }
+
+    pub fn with_render_scale(&mut self, render_scale: f32) -> &mut CameraBuilder {
+        self.render_scale = Some(render_scale);
+        self
+    }
+
+    pub fn with_upscale(&mut self, upscale: Upscale) -> &mut CameraBuilder {
+        self.upscale = Some(upscale);
+        self
+    }
+
+    pub fn with_dynamic_resolution(&mut self, dynamic_resolution: DynamicResolutionSettings) -> &mut CameraBuilder {
+        self.dynamic_resolution = Some(dynamic_resolution);
+        self
+    }
+
+    pub fn with_temporal(&mut self, temporal: TemporalSettings) -> &mut CameraBuilder {
+        self.temporal = Some(temporal);
+        self
+    }
+
+    pub fn with_fog(&mut self, fog: FogSettings) -> &mut CameraBuilder {
+        self.fog = Some(fog);
+        self
+    }
+
+    pub fn with_shading(&mut self, shading: ShadingSettings) -> &mut CameraBuilder {
+        self.shading = Some(shading);
+        self
+    }
+
+    /// Start from a stored pose, this overrides origin and vertical fov
+    pub fn with_pose(&mut self, pose: CameraPose) -> &mut CameraBuilder {
+        self.pose = Some(pose);
+        self
+    }
 }
 
 
 // Sets all camera variables in the shader 
-fn initial_uniforms(camera: &Camera, program: &mut Program) {
+fn initial_uniforms(camera: &mut Camera, program: &mut Program) {
     // TODO: don't unwrap ... (send error to caller) 
     program.set_i32("camera.image_width", camera.image_width).unwrap();
     program.set_i32("camera.image_height", camera.image_height).unwrap();
     
-    program.set_vector3_f32("camera.horizontal", camera.horizontal).unwrap();
-    program.set_vector3_f32("camera.vertical", camera.vertical).unwrap();
-    program.set_vector3_f32("camera.lower_left_corner", camera.lower_left_corner).unwrap();
-    program.set_vector3_f32("camera.origin", camera.origin).unwrap();
+    // computes the viewport vectors and sets them
+    camera.propagate_changes(program);
     
     program.set_i32("camera.samples_per_pixel", camera.settings.samples_per_pixel).unwrap();
     program.set_i32("camera.max_bounce", camera.settings.max_bounce).unwrap();
+    projection_uniforms(&camera.settings, program);
+    camera.settings.fog.apply(program).unwrap();
+    camera.settings.shading.apply(program).unwrap();
+}
+
+fn projection_uniforms(settings: &CameraSettings, program: &mut Program) {
+    program.set_i32("camera.projection", settings.projection as i32).unwrap();
+    program.set_f32("camera.ortho_scale", settings.ortho_scale).unwrap();
+    program.set_f32("camera.fisheye_fov", settings.fisheye_fov.to_radians()).unwrap();
 }
\ No newline at end of file
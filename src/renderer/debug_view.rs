@@ -0,0 +1,57 @@
+use super::{InitializeErr, program::Program};
+
+/// Raytracer output for debugging traversal and shading, must match the DEBUG_* constants in raytracer.comp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugView {
+    Off = 0,
+    // octree traversal iterations of the primary ray relative to max_iter
+    Iterations,
+    // bounces relative to max_bounce
+    Bounces,
+    // octree level of the primary hit relative to max_depth
+    HitDepth,
+    Normals,
+    Albedo,
+    // rays that ran out of traversal iterations are red
+    IterationLimit,
+    // voxel ambient occlusion of the primary hit
+    AmbientOcclusion,
+    // albedo lit by an occluded ambient light and a directional light, without bounces
+    Preview,
+}
+
+impl DebugView {
+    const ALL: [DebugView; 9] = [
+        DebugView::Off,
+        DebugView::Iterations,
+        DebugView::Bounces,
+        DebugView::HitDepth,
+        DebugView::Normals,
+        DebugView::Albedo,
+        DebugView::IterationLimit,
+        DebugView::AmbientOcclusion,
+        DebugView::Preview,
+    ];
+
+    pub fn next(self) -> DebugView {
+        DebugView::ALL[(self as usize + 1) % DebugView::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugView::Off => "off",
+            DebugView::Iterations => "traversal iterations",
+            DebugView::Bounces => "bounce count",
+            DebugView::HitDepth => "hit depth",
+            DebugView::Normals => "normals",
+            DebugView::Albedo => "albedo",
+            DebugView::IterationLimit => "iteration limit",
+            DebugView::AmbientOcclusion => "ambient occlusion",
+            DebugView::Preview => "preview",
+        }
+    }
+
+    pub fn apply(&self, program: &mut Program) -> Result<(), InitializeErr> {
+        program.set_i32("debug_view", *self as i32)
+    }
+}
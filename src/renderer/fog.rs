@@ -0,0 +1,38 @@
+use cgmath::Vector3;
+use serde::{Serialize, Deserialize};
+
+use super::{InitializeErr, program::Program};
+
+/// Homogeneous participating medium that fills the bounds of the octree
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FogSettings {
+    pub enabled: bool,
+    // extinction per voxel length at the deepest level
+    pub density: f32,
+    // scattering albedo, the fraction of the extinction that is scattered instead of absorbed
+    pub albedo: (f32, f32, f32),
+    // henyey greenstein g, positive values scatter forward and negative backward
+    pub anisotropy: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            density: 0.002,
+            albedo: (0.9, 0.9, 0.9),
+            anisotropy: 0.3,
+        }
+    }
+}
+
+impl FogSettings {
+    pub fn apply(&self, program: &mut Program) -> Result<(), InitializeErr> {
+        let density = if self.enabled { self.density.max(0.0) } else { 0.0 };
+        program.set_f32("fog.density", density)?;
+        let (r, g, b) = self.albedo;
+        program.set_vector3_f32("fog.albedo", Vector3::new(r, g, b).map(|c| c.clamp(0.0, 1.0)))?;
+        program.set_f32("fog.anisotropy", self.anisotropy.clamp(-0.99, 0.99))
+    }
+}
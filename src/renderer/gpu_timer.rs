@@ -0,0 +1,134 @@
+use gl::types::{GLint, GLuint, GLuint64};
+
+/// Passes of a frame that are timed on the gpu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuPass {
+    Raytrace = 0,
+    OctreeUpdate,
+    Blit,
+    Overlay,
+}
+
+impl GpuPass {
+    pub const ALL: [GpuPass; 4] = [GpuPass::Raytrace, GpuPass::OctreeUpdate, GpuPass::Blit, GpuPass::Overlay];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GpuPass::Raytrace => "raytrace",
+            GpuPass::OctreeUpdate => "octree update",
+            GpuPass::Blit => "blit",
+            GpuPass::Overlay => "overlay",
+        }
+    }
+}
+
+/// Results are read as soon as they are available, and at the latest this many frames after they were issued, when
+/// the cpu waits for them because the query is needed again. end_frame has then returned every timing of a frame
+pub const QUERY_FRAMES: usize = 4;
+
+/// GL_TIME_ELAPSED queries around each pass. Only one query can be active at a time in GL,
+/// so passes can not be nested. A pass that runs several times in a frame is only timed the first time
+pub struct GpuTimer {
+    queries: [[GLuint; QUERY_FRAMES]; GpuPass::ALL.len()],
+    // frame the query was issued in, none when its result has been read
+    issued: [[Option<usize>; QUERY_FRAMES]; GpuPass::ALL.len()],
+    frame: usize,
+    active: Option<GpuPass>,
+}
+
+impl GpuTimer {
+    pub fn new() -> GpuTimer {
+        let mut queries = [[0; QUERY_FRAMES]; GpuPass::ALL.len()];
+        for pass_queries in queries.iter_mut() {
+            unsafe {
+                gl::GenQueries(QUERY_FRAMES as i32, pass_queries.as_mut_ptr());
+            }
+        }
+
+        GpuTimer {
+            queries,
+            issued: [[None; QUERY_FRAMES]; GpuPass::ALL.len()],
+            frame: 0,
+            active: None,
+        }
+    }
+
+    pub fn begin(&mut self, pass: GpuPass) {
+        let slot = self.frame % QUERY_FRAMES;
+        // end_frame has read older results of the slot
+        if self.active.is_some() || self.issued[pass as usize][slot].is_some() {
+            return;
+        }
+
+        unsafe {
+            gl::BeginQuery(gl::TIME_ELAPSED, self.queries[pass as usize][slot]);
+        }
+        self.issued[pass as usize][slot] = Some(self.frame);
+        self.active = Some(pass);
+    }
+
+    pub fn end(&mut self, pass: GpuPass) {
+        if self.active != Some(pass) {
+            return;
+        }
+
+        unsafe {
+            gl::EndQuery(gl::TIME_ELAPSED);
+        }
+        self.active = None;
+    }
+
+    /// Move on to the next frame and collect the timings, in milliseconds, that became available, as the frame they
+    /// were issued in and the pass. Waits for the timings of the frame whose queries are used next
+    pub fn end_frame(&mut self) -> Vec<(usize, GpuPass, f32)> {
+        self.frame += 1;
+
+        let mut timings = Vec::<(usize, GpuPass, f32)>::with_capacity(GpuPass::ALL.len());
+        for pass in GpuPass::ALL.iter() {
+            for slot in 0..QUERY_FRAMES {
+                let frame = match self.issued[*pass as usize][slot] {
+                    Some(frame) => frame,
+                    None => continue,
+                };
+
+                let query = self.queries[*pass as usize][slot];
+                if frame + QUERY_FRAMES > self.frame {
+                    let mut available: GLint = 0;
+                    unsafe {
+                        gl::GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available);
+                    }
+                    if available == 0 {
+                        continue;
+                    }
+                }
+
+                let mut nanoseconds: GLuint64 = 0;
+                unsafe {
+                    gl::GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut nanoseconds);
+                }
+                self.issued[*pass as usize][slot] = None;
+                timings.push((frame, *pass, nanoseconds as f32 / 1_000_000.0));
+            }
+        }
+        timings.sort_by_key(|(frame, pass, _)| (*frame, *pass as usize));
+        timings
+    }
+
+    /// Wait for the gpu and collect the timings that end_frame has not returned yet
+    pub fn finish(&mut self) -> Vec<(usize, GpuPass, f32)> {
+        unsafe {
+            gl::Finish();
+        }
+        self.end_frame()
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        for pass_queries in self.queries.iter() {
+            unsafe {
+                gl::DeleteQueries(QUERY_FRAMES as i32, pass_queries.as_ptr());
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use super::materials::{MaterialDesc, MaterialKind, MaterialLibrary, PrincipledParams};
+
+/// A parameter of the edited material, with the range it is kept in and how much it changes per second
+/// while a key is held
+struct Field<'a> {
+    name: &'static str,
+    value: &'a mut f32,
+    min: f32,
+    max: f32,
+    rate: f32,
+}
+
+impl<'a> Field<'a> {
+    fn new(name: &'static str, value: &'a mut f32, min: f32, max: f32, rate: f32) -> Field<'a> {
+        Field { name, value, min, max, rate }
+    }
+}
+
+// kinds that the kind field steps through, imported materials become principled when they are edited
+const KIND_NAMES: [&str; 5] = ["Lambertian", "Metal", "Dielectric", "Principled", "Volume"];
+
+fn kind_index(kind: &MaterialKind) -> usize {
+    match kind {
+        MaterialKind::Lambertian => 0,
+        MaterialKind::Metal { .. } => 1,
+        MaterialKind::Dielectric { .. } => 2,
+        MaterialKind::Principled(_) | MaterialKind::Matl(_) | MaterialKind::GltfMetalRough(_) => 3,
+        MaterialKind::Volume { .. } => 4,
+    }
+}
+
+fn default_kind(index: usize) -> MaterialKind {
+    match index {
+        1 => MaterialKind::Metal { roughness: 0.3, complex_ior: None },
+        2 => MaterialKind::Dielectric { ir: 1.5, roughness: 0.0, absorption: 0.0, cauchy_b: 0.0 },
+        3 => MaterialKind::Principled(PrincipledParams::default()),
+        4 => MaterialKind::Volume { density: 0.5, anisotropy: 0.0 },
+        _ => MaterialKind::Lambertian,
+    }
+}
+
+// albedo and the parameters of the kind, the ranges keep the material valid
+fn fields(material: &mut MaterialDesc) -> Vec<Field<'_>> {
+    let (r, g, b) = &mut material.albedo;
+    let mut fields = vec![
+        Field::new("red", r, 0.0, 1.0, 0.5),
+        Field::new("green", g, 0.0, 1.0, 0.5),
+        Field::new("blue", b, 0.0, 1.0, 0.5),
+    ];
+    match &mut material.kind {
+        MaterialKind::Lambertian => (),
+        MaterialKind::Metal { roughness, .. } => fields.push(Field::new("roughness", roughness, 0.0, 1.0, 0.5)),
+        MaterialKind::Dielectric { ir, roughness, absorption, cauchy_b } => {
+            // ir of at least 1 keeps the red channel above 0 with the highest cauchy_b
+            fields.push(Field::new("ir", ir, 1.0, 3.0, 0.5));
+            fields.push(Field::new("roughness", roughness, 0.0, 1.0, 0.5));
+            fields.push(Field::new("absorption", absorption, 0.0, 5.0, 1.0));
+            fields.push(Field::new("cauchy_b", cauchy_b, 0.0, 0.05, 0.02));
+        },
+        MaterialKind::Principled(p) => {
+            fields.push(Field::new("metallic", &mut p.metallic, 0.0, 1.0, 0.5));
+            fields.push(Field::new("roughness", &mut p.roughness, 0.0, 1.0, 0.5));
+            fields.push(Field::new("specular", &mut p.specular, 0.0, 1.0, 0.5));
+            fields.push(Field::new("transmission", &mut p.transmission, 0.0, 1.0, 0.5));
+            fields.push(Field::new("ior", &mut p.ior, 1.0, 3.0, 0.5));
+            fields.push(Field::new("clearcoat", &mut p.clearcoat, 0.0, 1.0, 0.5));
+            fields.push(Field::new("clearcoat_roughness", &mut p.clearcoat_roughness, 0.0, 1.0, 0.5));
+        },
+        MaterialKind::Volume { density, anisotropy } => {
+            fields.push(Field::new("density", density, 0.01, 10.0, 1.0));
+            fields.push(Field::new("anisotropy", anisotropy, -0.95, 0.95, 0.5));
+        },
+        // converted by make_editable
+        MaterialKind::Matl(_) | MaterialKind::GltfMetalRough(_) => (),
+    }
+    fields
+}
+
+// imported materials are edited as the principled material they are converted to
+fn make_editable(material: &mut MaterialDesc) {
+    if let MaterialKind::Matl(_) | MaterialKind::GltfMetalRough(_) = material.kind {
+        let params = material.kind.principled(material.albedo).ok().flatten().unwrap_or_default();
+        material.kind = MaterialKind::Principled(params);
+    }
+}
+
+/// Edits one material of the library with keys. The first field is the material kind, the rest are the albedo and
+/// the parameters of the kind. Changes are made to the library, which the caller uploads and saves
+#[derive(Debug, Default)]
+pub struct MaterialEditor {
+    // index of the edited material
+    index: u32,
+    // selected field, 0 is the kind
+    field: usize,
+    // the library has edits that are not saved
+    unsaved: bool,
+}
+
+impl MaterialEditor {
+    /// Edit another material, starting at the kind field
+    pub fn select(&mut self, index: u32) {
+        if index != self.index {
+            self.index = index;
+            self.field = 0;
+        }
+    }
+
+    /// Select the next field, or a previous one with a negative step
+    pub fn step_field(&mut self, library: &MaterialLibrary, step: i32) {
+        let mut material = match library.materials.get(self.index as usize) {
+            Some(m) => m.clone(),
+            None => return,
+        };
+        make_editable(&mut material);
+        let count = fields(&mut material).len() as i32 + 1;
+        self.field = (self.field as i32 + step).rem_euclid(count) as usize;
+    }
+
+    /// Change the selected field by amount times its rate, or step through the kinds when the kind field is
+    /// selected and step is true. Returns true when the library changed
+    pub fn adjust(&mut self, library: &mut MaterialLibrary, amount: f32, step: bool) -> bool {
+        let field = self.field;
+        let material = match library.materials.get_mut(self.index as usize) {
+            Some(m) => m,
+            None => return false,
+        };
+        if field == 0 {
+            if !step || amount == 0.0 {
+                return false;
+            }
+            let count = KIND_NAMES.len() as i32;
+            let next = (kind_index(&material.kind) as i32 + amount.signum() as i32).rem_euclid(count);
+            material.kind = default_kind(next as usize);
+        } else {
+            if step {
+                return false;
+            }
+            make_editable(material);
+            let mut fields = fields(material);
+            let field = match fields.get_mut(field - 1) {
+                Some(f) => f,
+                None => return false,
+            };
+            *field.value = (*field.value + amount * field.rate).clamp(field.min, field.max);
+        }
+        self.unsaved = true;
+        true
+    }
+
+    /// Should be called when the library is written to its file, or replaced by the content of the file
+    pub fn saved(&mut self) {
+        self.unsaved = false;
+    }
+
+    /// Window with the fields of the edited material, the selected field is highlighted
+    pub fn show(&self, ctx: &egui::Context, library: &MaterialLibrary, paint_mode: bool) {
+        let mut material = match library.materials.get(self.index as usize) {
+            Some(m) => m.clone(),
+            None => return,
+        };
+        let imported = matches!(material.kind, MaterialKind::Matl(_) | MaterialKind::GltfMetalRough(_));
+        make_editable(&mut material);
+
+        egui::Window::new("Material")
+            .default_pos(egui::pos2(8.0, 480.0))
+            .resizable(false)
+            .show(ctx, |ui| {
+                let unsaved = if self.unsaved { " *" } else { "" };
+                ui.label(format!("{} '{}'{}", self.index, material.name, unsaved));
+                let (r, g, b) = material.albedo;
+                let (rect, _) = ui.allocate_exact_size(egui::vec2(48.0, 12.0), egui::Sense::hover());
+                let srgb = |c: f32| (c.clamp(0.0, 1.0).sqrt() * 255.0) as u8;
+                ui.painter().rect_filled(rect, 0.0, egui::Color32::from_rgb(srgb(r), srgb(g), srgb(b)));
+
+                let selected = |ui: &mut egui::Ui, index: usize, name: &str, value: String| {
+                    let text = egui::RichText::new(name);
+                    ui.label(if index == self.field { text.strong().color(egui::Color32::YELLOW) } else { text });
+                    ui.monospace(value);
+                    ui.end_row();
+                };
+                egui::Grid::new("material_fields").show(ui, |ui| {
+                    let kind = KIND_NAMES[kind_index(&material.kind)];
+                    selected(ui, 0, "kind", if imported { format!("{} (imported)", kind) } else { kind.to_string() });
+                    for (i, field) in fields(&mut material).iter().enumerate() {
+                        selected(ui, i + 1, field.name, format!("{:.3}", field.value));
+                    }
+                });
+                if paint_mode {
+                    ui.label("painting, left click paints and right click picks");
+                }
+                ui.label("up/down select, left/right change, ctrl + s saves");
+                ui.label("to settings/materials.ron next to the executable");
+            });
+    }
+}
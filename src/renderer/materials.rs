@@ -0,0 +1,558 @@
+use std::collections::{HashMap, HashSet};
+
+use cgmath::Vector3;
+
+use serde::{Serialize, Deserialize};
+
+use super::{Material, atlas::{AtlasDesc, FaceTiles}, procedural::{PROCEDURAL_FLOATS, Procedural}, vbo::VertexBufferObject};
+use crate::resources::Resources;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum MaterialKind {
+    Lambertian,
+    Metal {
+        // GGX roughness, 0 is a perfect mirror
+        roughness: f32,
+        // measured index of refraction, without it the albedo is the reflectance at normal incidence
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        complex_ior: Option<ComplexIor>,
+    },
+    Dielectric {
+        // index of refraction, at 589 nm for dispersive materials
+        ir: f32,
+        // GGX roughness, 0 is smooth glass
+        #[serde(default)]
+        roughness: f32,
+        // the albedo is the color after travelling 1 / absorption voxel lengths inside, 0 is clear
+        #[serde(default)]
+        absorption: f32,
+        // B of the cauchy equation in square micrometers, 0 disables dispersion
+        #[serde(default)]
+        cauchy_b: f32,
+    },
+    Principled(PrincipledParams),
+    // participating medium filling the voxel, the albedo is the scattering albedo
+    Volume {
+        // extinction per voxel length at the deepest level
+        density: f32,
+        // henyey greenstein g, positive values scatter forward and negative backward
+        #[serde(default)]
+        anisotropy: f32,
+    },
+    // MagicaVoxel MATL chunk dictionary, for example {"_type": "_metal", "_rough": "0.2"}
+    Matl(HashMap<String, String>),
+    GltfMetalRough(GltfMetalRough),
+}
+
+impl MaterialKind {
+    /// Parameters of the principled material for the principled and imported kinds
+    pub fn principled(&self, albedo: (f32, f32, f32)) -> Result<Option<PrincipledParams>, String> {
+        match self {
+            MaterialKind::Principled(params) => Ok(Some(*params)),
+            MaterialKind::Matl(matl) => PrincipledParams::from_matl(matl, albedo).map(Some),
+            MaterialKind::GltfMetalRough(gltf) => Ok(Some(PrincipledParams::from_gltf(gltf))),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Parameters of the principled material, the base color is the albedo of the material
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PrincipledParams {
+    // 0 is a dielectric, 1 a conductor with the base color as reflectance
+    pub metallic: f32,
+    pub roughness: f32,
+    // reflectance of dielectrics at normal incidence is 0.08 * specular, 0.5 is 4 %
+    pub specular: f32,
+    // fraction of the dielectric part that is refracted instead of diffuse
+    pub transmission: f32,
+    // index of refraction of the transmission
+    pub ior: f32,
+    // emitted radiance, may be above 1
+    pub emission: (f32, f32, f32),
+    // strength of a white coating over the other layers
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+}
+
+impl Default for PrincipledParams {
+    fn default() -> Self {
+        PrincipledParams {
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            transmission: 0.0,
+            ior: 1.5,
+            emission: (0.0, 0.0, 0.0),
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+        }
+    }
+}
+
+impl PrincipledParams {
+    /// Convert a MagicaVoxel MATL dictionary, the albedo should be the palette color of the material
+    pub fn from_matl(matl: &HashMap<String, String>, albedo: (f32, f32, f32)) -> Result<PrincipledParams, String> {
+        let value = |key: &str| -> Result<Option<f32>, String> {
+            matl.get(key)
+                .map(|v| v.parse::<f32>().map_err(|e| format!("MATL {} '{}' is not a number: {}", key, v, e)))
+                .transpose()
+        };
+        let material_type = matl.get("_type").map_or("_diffuse", |t| t.as_str());
+        if !["_diffuse", "_metal", "_glass", "_emit"].contains(&material_type) {
+            return Err(format!("MATL _type '{}' is not supported", material_type));
+        }
+        // older files only store the weight of the material type
+        let weight = value("_weight")?.unwrap_or(1.0);
+
+        let mut params = PrincipledParams::default();
+        if let Some(rough) = value("_rough")? {
+            params.roughness = rough;
+        }
+        if let Some(specular) = value("_spec")? {
+            params.specular = specular;
+        }
+        // MagicaVoxel stores the index of refraction minus 1
+        if let Some(ior) = value("_ior")? {
+            params.ior = 1.0 + ior;
+        }
+        params.metallic = match value("_metal")? {
+            Some(metal) => metal,
+            None if material_type == "_metal" => weight,
+            None => 0.0,
+        };
+        params.transmission = match value("_trans")? {
+            Some(trans) => trans,
+            None if material_type == "_glass" => weight,
+            None => 0.0,
+        };
+        if material_type == "_emit" {
+            // flux is a power of two on top of the emission, an approximation of the MagicaVoxel renderer
+            let strength = value("_emit")?.unwrap_or(weight) * 2f32.powf(value("_flux")?.unwrap_or(0.0));
+            params.emission = (albedo.0 * strength, albedo.1 * strength, albedo.2 * strength);
+        }
+        Ok(params)
+    }
+
+    /// Convert glTF metallic roughness values, the base color factor should be the albedo of the material
+    pub fn from_gltf(gltf: &GltfMetalRough) -> PrincipledParams {
+        let (r, g, b) = gltf.emissive_factor;
+        PrincipledParams {
+            metallic: gltf.metallic_factor,
+            roughness: gltf.roughness_factor,
+            // a specular factor of 1 is the 4 % reflectance of glTF dielectrics
+            specular: 0.5 * gltf.specular_factor,
+            transmission: gltf.transmission_factor,
+            ior: gltf.ior,
+            emission: (r * gltf.emissive_strength, g * gltf.emissive_strength, b * gltf.emissive_strength),
+            clearcoat: gltf.clearcoat_factor,
+            clearcoat_roughness: gltf.clearcoat_roughness_factor,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let unit_range = [
+            ("metallic", self.metallic),
+            ("roughness", self.roughness),
+            ("specular", self.specular),
+            ("transmission", self.transmission),
+            ("clearcoat", self.clearcoat),
+            ("clearcoat_roughness", self.clearcoat_roughness),
+        ];
+        for (name, value) in unit_range.iter() {
+            if !(0.0..=1.0).contains(value) {
+                return Err(format!("{} {} is outside of 0 - 1", name, value));
+            }
+        }
+        if self.ior <= 0.0 {
+            return Err(format!("ior {} must be above 0", self.ior));
+        }
+        let (r, g, b) = self.emission;
+        if [r, g, b].iter().any(|c| *c < 0.0) {
+            return Err(format!("emission {:?} can not be negative", self.emission));
+        }
+        Ok(())
+    }
+}
+
+/// glTF metallic roughness material factors with the ior, transmission, specular, clearcoat and emissive strength
+/// extensions, missing values use the glTF defaults
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GltfMetalRough {
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub emissive_factor: (f32, f32, f32),
+    pub emissive_strength: f32,
+    pub ior: f32,
+    pub transmission_factor: f32,
+    pub specular_factor: f32,
+    pub clearcoat_factor: f32,
+    pub clearcoat_roughness_factor: f32,
+}
+
+impl Default for GltfMetalRough {
+    fn default() -> Self {
+        GltfMetalRough {
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            emissive_factor: (0.0, 0.0, 0.0),
+            emissive_strength: 1.0,
+            ior: 1.5,
+            transmission_factor: 0.0,
+            specular_factor: 1.0,
+            clearcoat_factor: 0.0,
+            clearcoat_roughness_factor: 0.0,
+        }
+    }
+}
+
+/// Complex index of refraction eta + ik of a conductor, per color channel
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct ComplexIor {
+    pub eta: (f32, f32, f32),
+    pub k: (f32, f32, f32),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MaterialDesc {
+    pub name: String,
+    pub kind: MaterialKind,
+    // the absorption color of dielectrics, the base color of principled materials and the scattering albedo of volumes
+    #[serde(default = "default_albedo")]
+    pub albedo: (f32, f32, f32),
+    // atlas tiles that multiply the albedo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture: Option<FaceTiles>,
+    // solid texture that replaces the albedo, before the atlas tiles are applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub procedural: Option<Procedural>,
+}
+
+fn default_albedo() -> (f32, f32, f32) {
+    (1.0, 1.0, 1.0)
+}
+
+// 1 / 0.589^2 - 1 / 0.65^2, how much lower the index of refraction of the red channel is per unit of cauchy_b
+const RED_IOR_OFFSET: f32 = 0.5156;
+
+/// Materials loaded from settings/materials.ron, the position in the list is the material index stored in leaf nodes
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MaterialLibrary {
+    #[serde(default)]
+    pub atlas: Option<AtlasDesc>,
+    pub materials: Vec<MaterialDesc>,
+}
+
+impl MaterialLibrary {
+    /// Load and validate a material library
+    pub fn from_resources(res: &Resources, name: &str) -> Result<MaterialLibrary, String> {
+        let bytes = res.load_buffer(name)
+            .map_err(|e| format!("Error loading resource {}: {:?}", name, e))?;
+        let library: MaterialLibrary = ron::de::from_bytes(&bytes[0..])
+            .map_err(|e| format!("Error parsing {}: {}", name, e))?;
+        library.validate().map_err(|e| format!("Invalid material in {}: {}", name, e))?;
+        Ok(library)
+    }
+
+    /// Write the library to name, the comment lines at the top of the existing file are kept
+    pub fn save(&self, res: &Resources, name: &str) -> Result<(), String> {
+        let header: String = match res.load_buffer(name) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).lines()
+                .take_while(|line| line.starts_with("//"))
+                .map(|line| format!("{}\n", line))
+                .collect(),
+            Err(_) => String::new(),
+        };
+        // one line per material like the hand written file
+        let config = ron::ser::PrettyConfig::new().with_depth_limit(2).with_decimal_floats(true);
+        let body = ron::ser::to_string_pretty(self, config)
+            .map_err(|e| format!("Error serializing {}: {}", name, e))?;
+        res.write_buffer(name, format!("{}{}\n", header, body).as_bytes())
+            .map_err(|e| format!("Error writing resource {}: {:?}", name, e))
+    }
+
+    /// Check parameter ranges and that names are unique
+    pub fn validate(&self) -> Result<(), String> {
+        if self.materials.is_empty() {
+            return Err("at least one material is needed".to_string());
+        }
+
+        let mut names = HashSet::<&str>::with_capacity(self.materials.len());
+        for (i, material) in self.materials.iter().enumerate() {
+            if !names.insert(&material.name) {
+                return Err(format!("material {} reuses the name '{}'", i, material.name));
+            }
+
+            let (r, g, b) = material.albedo;
+            if [r, g, b].iter().any(|c| !(0.0..=1.0).contains(c)) {
+                return Err(format!("'{}' has albedo {:?} outside of 0 - 1", material.name, material.albedo));
+            }
+            let principled = material.kind.principled(material.albedo)
+                .map_err(|e| format!("'{}' {}", material.name, e))?;
+            if let Some(params) = principled {
+                params.validate().map_err(|e| format!("'{}' has {}", material.name, e))?;
+            }
+            if let Some(procedural) = material.procedural {
+                procedural.validate().map_err(|e| format!("'{}' has {}", material.name, e))?;
+            }
+            match material.kind {
+                MaterialKind::Metal { roughness, .. } | MaterialKind::Dielectric { roughness, .. } if !(0.0..=1.0).contains(&roughness) => {
+                    return Err(format!("'{}' has roughness {} outside of 0 - 1", material.name, roughness));
+                },
+                MaterialKind::Metal { complex_ior: Some(ComplexIor { eta, k }), .. }
+                    if [eta.0, eta.1, eta.2].iter().any(|c| *c <= 0.0) || [k.0, k.1, k.2].iter().any(|c| *c < 0.0) => {
+                    return Err(format!("'{}' needs eta above 0 and k of at least 0", material.name));
+                },
+                MaterialKind::Dielectric { ir, .. } if ir <= 0.0 => {
+                    return Err(format!("'{}' has ir {}, it must be above 0", material.name, ir));
+                },
+                MaterialKind::Dielectric { absorption, .. } if absorption < 0.0 => {
+                    return Err(format!("'{}' has absorption {}, it can not be negative", material.name, absorption));
+                },
+                // the index of refraction is lowest for red
+                MaterialKind::Dielectric { ir, cauchy_b, .. } if cauchy_b < 0.0 || ir - cauchy_b * RED_IOR_OFFSET <= 0.0 => {
+                    return Err(format!("'{}' has cauchy_b {}, it must be at least 0 and keep ir above 0 for red", material.name, cauchy_b));
+                },
+                MaterialKind::Volume { density, .. } if density <= 0.0 => {
+                    return Err(format!("'{}' has density {}, it must be above 0", material.name, density));
+                },
+                MaterialKind::Volume { anisotropy, .. } if anisotropy.abs() >= 1.0 => {
+                    return Err(format!("'{}' has anisotropy {} outside of -1 - 1", material.name, anisotropy));
+                },
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Albedo of a material with its procedural texture at point, which is in voxels at the deepest level from the
+    /// octree min point. Atlas tiles are not applied
+    pub fn surface_albedo(&self, index: u32, point: Vector3<f32>) -> Option<Vector3<f32>> {
+        let material = self.materials.get(index as usize)?;
+        let albedo = Vector3::new(material.albedo.0, material.albedo.1, material.albedo.2);
+        Some(material.procedural.map_or(albedo, |procedural| procedural.albedo(albedo, point)))
+    }
+
+    /// Check that a material index used by the scene exists
+    pub fn check_index(&self, index: u32) -> Result<(), String> {
+        if index as usize >= self.materials.len() {
+            return Err(format!("material index {} is out of range, there are {} materials", index, self.materials.len()));
+        }
+        Ok(())
+    }
+}
+
+// size of the Metal and Dielectric structs in raytracer.comp
+const METAL_FLOATS: usize = 8;
+const DIELECTRIC_FLOATS: usize = 4;
+const PRINCIPLED_FLOATS: usize = 10;
+const VOLUME_FLOATS: usize = 2;
+// materials without a texture
+const NO_TILE: i32 = -1;
+
+/// The Material, Albedo, Metal and Dielectric storage buffers (bindings 1 - 4) and the Principled, Volume,
+/// TextureTiles and Procedural buffers (bindings 8 - 11) of the raytracer
+pub struct MaterialBuffers {
+    materials: VertexBufferObject,
+    albedos: VertexBufferObject,
+    metals: VertexBufferObject,
+    dielectrics: VertexBufferObject,
+    principled: VertexBufferObject,
+    volumes: VertexBufferObject,
+    texture_tiles: VertexBufferObject,
+    procedurals: VertexBufferObject,
+}
+
+impl MaterialBuffers {
+    /// Should be created before the octree buffers, as binding changes the generic storage buffer binding
+    pub fn new(library: &MaterialLibrary) -> MaterialBuffers {
+        // created as array buffers so that uploads do not change the generic storage buffer binding,
+        // which Octree::update_vbo writes to
+        let buffer = |binding| {
+            let vbo = VertexBufferObject::new::<f32>(Vec::new(), gl::ARRAY_BUFFER, gl::STATIC_DRAW);
+            unsafe { gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, vbo.id()); }
+            vbo
+        };
+        let mut buffers = MaterialBuffers {
+            materials: buffer(1),
+            albedos: buffer(2),
+            metals: buffer(3),
+            dielectrics: buffer(4),
+            principled: buffer(8),
+            volumes: buffer(9),
+            texture_tiles: buffer(10),
+            procedurals: buffer(11),
+        };
+        buffers.upload(library);
+        buffers
+    }
+
+    /// Replace the content of the buffers with library
+    pub fn upload(&mut self, library: &MaterialLibrary) {
+        let mut materials = Vec::<u32>::with_capacity(library.materials.len() * 3);
+        let mut albedos = Vec::<f32>::with_capacity(library.materials.len() * 3);
+        let mut metals = Vec::<f32>::new();
+        let mut dielectrics = Vec::<f32>::new();
+        let mut principled = Vec::<f32>::new();
+        let mut volumes = Vec::<f32>::new();
+        let mut texture_tiles = Vec::<i32>::with_capacity(library.materials.len() * 3);
+        let mut procedurals = Vec::<f32>::with_capacity(library.materials.len() * PROCEDURAL_FLOATS);
+
+        for (i, material) in library.materials.iter().enumerate() {
+            let (material_type, attribute_index) = match &material.kind {
+                MaterialKind::Lambertian => (Material::Lambertian, 0),
+                MaterialKind::Metal { roughness, complex_ior } => {
+                    // |Roughness |Complex ior |Eta rgb |K rgb |
+                    let attribute_index = metals.len() / METAL_FLOATS;
+                    let (has_ior, ior) = match complex_ior {
+                        Some(ior) => (1.0, *ior),
+                        None => (0.0, ComplexIor { eta: (1.0, 1.0, 1.0), k: (0.0, 0.0, 0.0) }),
+                    };
+                    metals.extend_from_slice(&[*roughness, has_ior, ior.eta.0, ior.eta.1, ior.eta.2, ior.k.0, ior.k.1, ior.k.2]);
+                    (Material::Metal, attribute_index)
+                },
+                MaterialKind::Dielectric { ir, roughness, absorption, cauchy_b } => {
+                    // |Ir |Roughness |Absorption |Cauchy b |
+                    let attribute_index = dielectrics.len() / DIELECTRIC_FLOATS;
+                    dielectrics.extend_from_slice(&[*ir, *roughness, *absorption, *cauchy_b]);
+                    (Material::Dielectric, attribute_index)
+                },
+                MaterialKind::Principled(_) | MaterialKind::Matl(_) | MaterialKind::GltfMetalRough(_) => {
+                    // |Metallic |Roughness |Specular |Transmission |Ior |Emission rgb |Clearcoat |Clearcoat roughness |
+                    let attribute_index = principled.len() / PRINCIPLED_FLOATS;
+                    // the library is validated before it is uploaded
+                    let p = material.kind.principled(material.albedo).ok().flatten().unwrap_or_default();
+                    principled.extend_from_slice(&[
+                        p.metallic, p.roughness, p.specular, p.transmission, p.ior,
+                        p.emission.0, p.emission.1, p.emission.2, p.clearcoat, p.clearcoat_roughness,
+                    ]);
+                    (Material::Principled, attribute_index)
+                },
+                MaterialKind::Volume { density, anisotropy } => {
+                    // |Density |Anisotropy |
+                    let attribute_index = volumes.len() / VOLUME_FLOATS;
+                    volumes.extend_from_slice(&[*density, *anisotropy]);
+                    (Material::Volume, attribute_index)
+                },
+            };
+            // |Type  |Attrib |Albedo |
+            materials.extend_from_slice(&[material_type as u32, attribute_index as u32, i as u32]);
+            albedos.extend_from_slice(&[material.albedo.0, material.albedo.1, material.albedo.2]);
+            // |Top |Side |Bottom |
+            match material.texture {
+                Some(tiles) => texture_tiles.extend_from_slice(&[tiles.top as i32, tiles.side as i32, tiles.bottom as i32]),
+                None => texture_tiles.extend_from_slice(&[NO_TILE; 3]),
+            }
+            // type 0 is no procedural texture
+            match material.procedural {
+                Some(procedural) => procedurals.extend_from_slice(&procedural.to_floats()),
+                None => procedurals.extend_from_slice(&[0.0; PROCEDURAL_FLOATS]),
+            }
+        }
+
+        // buffers without any content can not be bound
+        if metals.is_empty() {
+            metals.resize(METAL_FLOATS, 0.0);
+        }
+        if dielectrics.is_empty() {
+            dielectrics.resize(DIELECTRIC_FLOATS, 0.0);
+        }
+        if principled.is_empty() {
+            principled.resize(PRINCIPLED_FLOATS, 0.0);
+        }
+        if volumes.is_empty() {
+            volumes.resize(VOLUME_FLOATS, 0.0);
+        }
+
+        self.materials.update(&materials, gl::STATIC_DRAW);
+        self.albedos.update(&albedos, gl::STATIC_DRAW);
+        self.metals.update(&metals, gl::STATIC_DRAW);
+        self.dielectrics.update(&dielectrics, gl::STATIC_DRAW);
+        self.principled.update(&principled, gl::STATIC_DRAW);
+        self.volumes.update(&volumes, gl::STATIC_DRAW);
+        self.texture_tiles.update(&texture_tiles, gl::STATIC_DRAW);
+        self.procedurals.update(&procedurals, gl::STATIC_DRAW);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matl(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn matl_ior_is_stored_minus_one() {
+        let params = PrincipledParams::from_matl(&matl(&[("_type", "_glass"), ("_ior", "0.5")]), (1.0, 1.0, 1.0)).unwrap();
+        assert_close(params.ior, 1.5);
+        let params = PrincipledParams::from_matl(&matl(&[("_type", "_glass")]), (1.0, 1.0, 1.0)).unwrap();
+        assert_close(params.ior, PrincipledParams::default().ior);
+    }
+
+    #[test]
+    fn matl_weight_is_the_fallback_of_the_type() {
+        let params = PrincipledParams::from_matl(&matl(&[("_type", "_metal"), ("_weight", "0.4")]), (1.0, 1.0, 1.0)).unwrap();
+        assert_close(params.metallic, 0.4);
+        assert_close(params.transmission, 0.0);
+        let params = PrincipledParams::from_matl(&matl(&[("_type", "_metal"), ("_weight", "0.4"), ("_metal", "0.8")]), (1.0, 1.0, 1.0)).unwrap();
+        assert_close(params.metallic, 0.8);
+
+        let params = PrincipledParams::from_matl(&matl(&[("_type", "_glass"), ("_weight", "0.3")]), (1.0, 1.0, 1.0)).unwrap();
+        assert_close(params.transmission, 0.3);
+        assert_close(params.metallic, 0.0);
+        let params = PrincipledParams::from_matl(&matl(&[("_type", "_glass")]), (1.0, 1.0, 1.0)).unwrap();
+        assert_close(params.transmission, 1.0);
+
+        let params = PrincipledParams::from_matl(&matl(&[("_weight", "0.3")]), (1.0, 1.0, 1.0)).unwrap();
+        assert_eq!(params, PrincipledParams::default());
+    }
+
+    #[test]
+    fn matl_emission_is_scaled_by_flux() {
+        let albedo = (1.0, 0.5, 0.25);
+        let params = PrincipledParams::from_matl(&matl(&[("_type", "_emit"), ("_emit", "0.5"), ("_flux", "2")]), albedo).unwrap();
+        assert_close(params.emission.0, 2.0);
+        assert_close(params.emission.1, 1.0);
+        assert_close(params.emission.2, 0.5);
+        let params = PrincipledParams::from_matl(&matl(&[("_type", "_emit"), ("_weight", "0.25")]), albedo).unwrap();
+        assert_close(params.emission.0, 0.25);
+        // only emissive materials emit
+        let params = PrincipledParams::from_matl(&matl(&[("_type", "_metal"), ("_emit", "1.0")]), albedo).unwrap();
+        assert_eq!(params.emission, (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn matl_rejects_unsupported_types_and_values() {
+        for material_type in ["_blend", "_media", "_plastic", "_unknown"].iter() {
+            assert!(PrincipledParams::from_matl(&matl(&[("_type", material_type)]), (1.0, 1.0, 1.0)).is_err());
+        }
+        assert!(PrincipledParams::from_matl(&matl(&[("_rough", "rough")]), (1.0, 1.0, 1.0)).is_err());
+        let kind = MaterialKind::Matl(matl(&[("_type", "_media")]));
+        assert!(kind.principled((1.0, 1.0, 1.0)).is_err());
+    }
+
+    #[test]
+    fn gltf_specular_factor_is_halved() {
+        let gltf = GltfMetalRough {
+            specular_factor: 0.6,
+            emissive_factor: (1.0, 0.5, 0.0),
+            emissive_strength: 4.0,
+            ..GltfMetalRough::default()
+        };
+        let params = PrincipledParams::from_gltf(&gltf);
+        assert_close(params.specular, 0.3);
+        assert_eq!(params.emission, (4.0, 2.0, 0.0));
+        assert_close(PrincipledParams::from_gltf(&GltfMetalRough::default()).specular, 0.5);
+        assert_close(params.metallic, 1.0);
+        assert_close(params.roughness, 1.0);
+        assert_close(params.ior, 1.5);
+    }
+}
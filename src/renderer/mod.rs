@@ -12,8 +12,20 @@ pub mod program;
 pub mod texture;
 pub mod vao;
 pub mod vbo;
+pub mod materials;
+pub mod material_editor;
+pub mod atlas;
+pub mod procedural;
 pub mod octree;
+pub mod octree_overlay;
+pub mod ui_overlay;
 pub mod compute_shader;
+pub mod temporal;
+pub mod fog;
+pub mod shading;
+pub mod debug_view;
+pub mod gpu_timer;
+pub mod ui_painter;
 
 mod utils;
 
@@ -21,6 +33,8 @@ pub enum Material {
     Lambertian = 0,
     Metal,
     Dielectric,
+    Principled,
+    Volume,
 }
 
 // TODO: split this into different errors
@@ -7,6 +7,251 @@ pub const EMPTY: u32 = 0;
 pub const PARENT: u32 = 1;
 pub const LEAF: u32 = 2;
 
+pub fn node_type_name(node_type: u32) -> &'static str {
+    match node_type {
+        EMPTY => "EMPTY",
+        PARENT => "PARENT",
+        LEAF => "LEAF",
+        _ => "UNKNOWN",
+    }
+}
+
+/// A node of the host octree in unit space
+#[derive(Debug, Clone, Copy)]
+pub struct NodeInfo {
+    // indirect cell that holds the node, and the node's child index in that cell
+    pub cell: u32,
+    pub child: u32,
+    pub node_type: u32,
+    pub value: u32,
+    // 0 for the children of the root cell
+    pub level: i32,
+    pub min: Vector3<f32>,
+    pub size: f32,
+}
+
+impl NodeInfo {
+    /// Outwards normal of the face of the node closest to point
+    pub fn face_normal(&self, point: Vector3<f32>) -> Vector3<f32> {
+        let mut normal = Vector3::new(0.0, 0.0, 0.0);
+        let mut closest = f32::INFINITY;
+        for axis in 0..3 {
+            let to_min = (point[axis] - self.min[axis]).abs();
+            let to_max = (self.min[axis] + self.size - point[axis]).abs();
+            if to_min.min(to_max) < closest {
+                closest = to_min.min(to_max);
+                normal = Vector3::new(0.0, 0.0, 0.0);
+                normal[axis] = if to_min < to_max { -1.0 } else { 1.0 };
+            }
+        }
+        normal
+    }
+}
+
+/// Host side copy of the indirect cells. Updates are applied the same way as octree_update.comp does
+/// so that the host can answer occupancy queries without reading back gpu memory
+pub struct HostOctree {
+    // (value, type) pairs, 8 nodes per cell
+    cells: Vec<u32>,
+    max_depth: i32,
+    active_cell_count: u32,
+}
+
+impl HostOctree {
+    pub fn new(cells: Vec<u32>, max_depth: i32, active_cell_count: i32) -> HostOctree {
+        HostOctree {
+            cells,
+            max_depth,
+            active_cell_count: active_cell_count as u32,
+        }
+    }
+
+    pub fn max_depth(&self) -> i32 {
+        self.max_depth
+    }
+
+    /// Cells that are in use, new cells are allocated after these
+    pub fn active_cell_count(&self) -> u32 {
+        self.active_cell_count
+    }
+
+    /// Highest material index stored in a leaf, none when there are no leaves
+    pub fn max_leaf_value(&self) -> Option<u32> {
+        let active_len = (self.active_cell_count as usize * 16).min(self.cells.len());
+        self.cells[..active_len].chunks_exact(2).filter(|node| node[1] == LEAF).map(|node| node[0]).max()
+    }
+
+    /// Amount of leaf nodes in the active cells
+    pub fn leaf_count(&self) -> usize {
+        let active_len = (self.active_cell_count as usize * 16).min(self.cells.len());
+        self.cells[..active_len].chunks_exact(2).filter(|node| node[1] == LEAF).count()
+    }
+
+    /// Amount of voxels along each axis at the deepest level
+    pub fn resolution(&self) -> i32 {
+        1 << self.max_depth
+    }
+
+    // index of node (value) in cells for a child of cell
+    fn node_index(cell: u32, child: Vector3<i32>) -> usize {
+        // Same layout as IndirectCellIndex in the shaders
+        2 * (8 * cell as usize + (child.z + 2 * (child.y + 2 * child.x)) as usize)
+    }
+
+    // child of the node at level that contains voxel
+    fn child_at_level(&self, voxel: Vector3<i32>, level: i32) -> Vector3<i32> {
+        let bit = self.max_depth - 1 - level;
+        Vector3::new((voxel.x >> bit) & 1, (voxel.y >> bit) & 1, (voxel.z >> bit) & 1)
+    }
+
+    fn node(&self, index: usize) -> (u32, u32) {
+        match (self.cells.get(index), self.cells.get(index + 1)) {
+            (Some(value), Some(node_type)) => (*value, *node_type),
+            _ => (0, EMPTY),
+        }
+    }
+
+    fn set_node(&mut self, index: usize, value: u32, node_type: u32) {
+        if self.cells.len() < index + 2 {
+            self.cells.resize(index + 2, EMPTY);
+        }
+        self.cells[index] = value;
+        self.cells[index + 1] = node_type;
+    }
+
+    /// Leaf that contains voxel, returns the leaf value (material index) and the level it was found at
+    pub fn leaf(&self, voxel: Vector3<i32>) -> Option<(u32, i32)> {
+        let resolution = self.resolution();
+        if voxel.x < 0 || voxel.y < 0 || voxel.z < 0 || voxel.x >= resolution || voxel.y >= resolution || voxel.z >= resolution {
+            return None;
+        }
+
+        let mut cell = 0;
+        for level in 0..self.max_depth {
+            let (value, node_type) = self.node(HostOctree::node_index(cell, self.child_at_level(voxel, level)));
+            match node_type {
+                PARENT => cell = value,
+                LEAF => return Some((value, level)),
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    pub fn is_solid(&self, voxel: Vector3<i32>) -> bool {
+        self.leaf(voxel).is_some()
+    }
+
+    /// Deepest node that contains point, which is in unit space. This is an EMPTY or LEAF node unless the tree is malformed
+    pub fn node_at(&self, point: Vector3<f32>) -> Option<NodeInfo> {
+        if point.x < 0.0 || point.y < 0.0 || point.z < 0.0 || point.x >= 1.0 || point.y >= 1.0 || point.z >= 1.0 {
+            return None;
+        }
+        let resolution = self.resolution() as f32;
+        let voxel = Vector3::new((point.x * resolution) as i32, (point.y * resolution) as i32, (point.z * resolution) as i32);
+
+        let mut cell = 0;
+        let mut min = Vector3::new(0.0, 0.0, 0.0);
+        let mut size = 1.0;
+        for level in 0..self.max_depth {
+            let child = self.child_at_level(voxel, level);
+            size *= 0.5;
+            min += Vector3::new(child.x as f32, child.y as f32, child.z as f32) * size;
+            let (value, node_type) = self.node(HostOctree::node_index(cell, child));
+            if node_type != PARENT || level == self.max_depth - 1 {
+                return Some(NodeInfo {
+                    cell,
+                    child: (child.z + 2 * (child.y + 2 * child.x)) as u32,
+                    node_type,
+                    value,
+                    level,
+                    min,
+                    size,
+                });
+            }
+            cell = value;
+        }
+        None
+    }
+
+    /// Nodes that are not EMPTY down to and including max_level, parents before their children
+    pub fn nodes(&self, max_level: i32) -> Vec<NodeInfo> {
+        let mut nodes = Vec::<NodeInfo>::new();
+        // cells to visit with their min point, size and level
+        let mut stack = vec![(0u32, Vector3::new(0.0, 0.0, 0.0), 1.0f32, 0)];
+        while let Some((cell, cell_min, cell_size, level)) = stack.pop() {
+            let size = cell_size * 0.5;
+            for child in 0..8u32 {
+                let offset = Vector3::new((child >> 2) & 1, (child >> 1) & 1, child & 1);
+                let (value, node_type) = self.node(2 * (8 * cell as usize + child as usize));
+                if node_type == EMPTY {
+                    continue;
+                }
+                let min = cell_min + Vector3::new(offset.x as f32, offset.y as f32, offset.z as f32) * size;
+                nodes.push(NodeInfo { cell, child, node_type, value, level, min, size });
+                if node_type == PARENT && level < max_level.min(self.max_depth - 1) {
+                    stack.push((value, min, size, level + 1));
+                }
+            }
+        }
+        nodes
+    }
+
+    /// First LEAF node along a ray in unit space, and the distance to it
+    pub fn raycast(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<(NodeInfo, f32)> {
+        const MAX_STEPS: usize = 1024;
+        let (t_enter, t_exit) = box_intersection(Vector3::new(0.0, 0.0, 0.0), 1.0, origin, direction)?;
+        // nudge past node boundaries so that the next lookup is in the next node
+        let epsilon = 0.0025 / self.resolution() as f32;
+
+        let mut t = t_enter.max(0.0);
+        for _ in 0..MAX_STEPS {
+            if t > t_exit {
+                return None;
+            }
+            let node = self.node_at(origin + direction * (t + epsilon))?;
+            if node.node_type == LEAF {
+                return Some((node, t));
+            }
+            let (_, node_exit) = box_intersection(node.min, node.size, origin, direction)?;
+            t = node_exit.max(t + epsilon);
+        }
+        None
+    }
+
+    /// Write a node along the path to voxel, creating parents where needed (see treeLookupLeaf)
+    pub fn apply_delta(&mut self, voxel: Vector3<i32>, node_type: u32, value: u32) {
+        let mut cell = 0;
+        let mut index = 0;
+        for level in 0..self.max_depth - 1 {
+            index = HostOctree::node_index(cell, self.child_at_level(voxel, level));
+            if self.node(index).1 == EMPTY {
+                self.set_node(index, self.active_cell_count, PARENT);
+                self.active_cell_count += 1;
+            }
+            cell = self.node(index).0;
+        }
+        self.set_node(index, value, node_type);
+    }
+}
+
+// Entry and exit distance of a ray through an axis aligned box
+fn box_intersection(min: Vector3<f32>, size: f32, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<(f32, f32)> {
+    let mut t_enter = f32::NEG_INFINITY;
+    let mut t_exit = f32::INFINITY;
+    for axis in 0..3 {
+        let inv = 1.0 / direction[axis];
+        let t0 = (min[axis] - origin[axis]) * inv;
+        let t1 = (min[axis] + size - origin[axis]) * inv;
+        t_enter = t_enter.max(t0.min(t1));
+        t_exit = t_exit.min(t0.max(t1));
+    }
+    if t_enter > t_exit || t_exit < 0.0 {
+        return None;
+    }
+    Some((t_enter, t_exit))
+}
+
 // TODO: builder?
 pub struct Octree {
     min_point: Vector3<f32>,
@@ -16,6 +261,9 @@ pub struct Octree {
     active_cell_count: i32,
     max_traversal_iter: i32,
     pub vao: VertexArrayObject,
+    pub host: HostOctree,
+    // storage buffer of the indirect cells (binding 0)
+    cells_buffer: u32,
     
     // distance between each block min point
     block_distance: f32, 
@@ -26,6 +274,7 @@ impl Octree {
     pub fn new(min_point: Vector3<f32>, scale: f32, max_depth: i32, cell_count: i32, active_cell_count: i32, max_traversal_iter: i32, vao: VertexArrayObject) -> Result<Octree, InitializeErr> {
         let block_distance = scale / 2f32.pow(max_depth);
         Ok(Octree {
+            host: HostOctree::new(Vec::new(), max_depth, active_cell_count),
             min_point,
             scale, 
             max_depth,
@@ -33,10 +282,17 @@ impl Octree {
             active_cell_count,
             max_traversal_iter,
             vao,
+            cells_buffer: 0,
             block_distance
         })
     }
 
+    /// Give the host a copy of the cells uploaded to cells_buffer, should be called before any update
+    pub fn mirror_cells(&mut self, cells: Vec<u32>, cells_buffer: u32) {
+        self.host = HostOctree::new(cells, self.max_depth, self.active_cell_count);
+        self.cells_buffer = cells_buffer;
+    }
+
     pub fn init_global_buffers(&self) -> Result<(), InitializeErr> {
         use super::{vbo, vao};
 
@@ -150,6 +406,11 @@ impl Octree {
         Ok(())
     }
 
+    /// Amount of cells that the gpu buffer has room for
+    pub fn cell_count(&self) -> i32 {
+        self.cell_count
+    }
+
     pub fn block_distance(&self) -> f32 {
         self.block_distance
     }
@@ -167,9 +428,36 @@ impl Octree {
         && point.x <= self.min_point.x + self.scale && point.y <= self.min_point.y + self.scale && point.z <= self.min_point.z + self.scale
     }
 
-    pub fn update_vbo(&self, delta: &Vec::<f32>, len: usize, update_compute: &ComputeShader) {
+    /// Change the material of a leaf without changing the geometry. The leaf is written in place, so leaves above
+    /// the deepest level are repainted as a whole instead of being split like update_vbo would
+    pub fn paint_leaf(&mut self, node: &NodeInfo, value: u32) {
+        if node.node_type != LEAF {
+            return;
+        }
+        let index = 2 * (8 * node.cell as usize + node.child as usize);
+        self.host.set_node(index, value, LEAF);
+
+        // bound as an array buffer so that the generic storage buffer binding used by update_vbo is kept
+        let offset = (index * std::mem::size_of::<u32>()) as gl::types::GLintptr;
+        let size = std::mem::size_of::<u32>() as gl::types::GLsizeiptr;
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.cells_buffer);
+            gl::BufferSubData(gl::ARRAY_BUFFER, offset, size, &value as *const u32 as *const gl::types::GLvoid);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    // delta is a list of [x, y, z, type, value] where the position is in octree unit space
+    pub fn update_vbo(&mut self, delta: &Vec::<f32>, len: usize, update_compute: &ComputeShader) {
         const LOCAL_GROUP_SIZE_X: f32 = 32.0 * 32.0;
 
+        let resolution = self.host.resolution() as f32;
+        for node in delta[..len].chunks_exact(5) {
+            let voxel = Vector3::new(node[0], node[1], node[2]) * resolution;
+            let voxel = Vector3::new(voxel.x.floor() as i32, voxel.y.floor() as i32, voxel.z.floor() as i32);
+            self.host.apply_delta(voxel, node[3] as u32, node[4] as u32);
+        }
+
         let size = (len * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr; // size of data in bytes
         unsafe { gl::BufferSubData(gl::SHADER_STORAGE_BUFFER, 0, size, delta.as_ptr() as *const gl::types::GLvoid); }
         
@@ -181,4 +469,109 @@ impl Octree {
             update_compute.dispatch_compute(dispatch_count, 1, 1)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // depth 3 is 8 voxels along each axis, edits make leaves of 2 voxels at level 1 like octree_update.comp
+    fn tree() -> HostOctree {
+        let mut tree = HostOctree::new(vec![EMPTY; 16], 3, 1);
+        tree.apply_delta(Vector3::new(0, 0, 0), LEAF, 5);
+        tree.apply_delta(Vector3::new(4, 0, 0), LEAF, 7);
+        tree
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn apply_delta_allocates_parents_and_writes_leaves() {
+        let tree = tree();
+        // the atomic counter is incremented for every empty node on the path, also the one that becomes the leaf
+        assert_eq!(tree.active_cell_count(), 5);
+        assert_eq!(tree.leaf_count(), 2);
+        assert_eq!(tree.max_leaf_value(), Some(7));
+
+        assert_eq!(tree.leaf(Vector3::new(0, 0, 0)), Some((5, 1)));
+        assert_eq!(tree.leaf(Vector3::new(1, 1, 1)), Some((5, 1)));
+        assert_eq!(tree.leaf(Vector3::new(5, 1, 0)), Some((7, 1)));
+        assert!(!tree.is_solid(Vector3::new(2, 0, 0)));
+        assert!(!tree.is_solid(Vector3::new(7, 7, 7)));
+        assert!(!tree.is_solid(Vector3::new(-1, 0, 0)));
+        assert!(!tree.is_solid(Vector3::new(8, 0, 0)));
+
+        let mut tree = tree;
+        tree.apply_delta(Vector3::new(1, 0, 1), EMPTY, 0);
+        assert!(!tree.is_solid(Vector3::new(0, 0, 0)));
+        assert_eq!(tree.active_cell_count(), 5);
+    }
+
+    #[test]
+    fn node_at_returns_the_deepest_node() {
+        let tree = tree();
+
+        let leaf = tree.node_at(Vector3::new(0.1, 0.2, 0.1)).unwrap();
+        assert_eq!((leaf.node_type, leaf.value, leaf.level), (LEAF, 5, 1));
+        assert_close(leaf.size, 0.25);
+        assert_eq!(leaf.min, Vector3::new(0.0, 0.0, 0.0));
+
+        let empty = tree.node_at(Vector3::new(0.3, 0.1, 0.1)).unwrap();
+        assert_eq!((empty.node_type, empty.level), (EMPTY, 1));
+        assert_eq!(empty.min, Vector3::new(0.25, 0.0, 0.0));
+
+        let empty = tree.node_at(Vector3::new(0.9, 0.9, 0.9)).unwrap();
+        assert_eq!((empty.node_type, empty.level), (EMPTY, 0));
+        assert_close(empty.size, 0.5);
+        assert_eq!(empty.min, Vector3::new(0.5, 0.5, 0.5));
+
+        assert!(tree.node_at(Vector3::new(1.0, 0.5, 0.5)).is_none());
+        assert!(tree.node_at(Vector3::new(-0.1, 0.5, 0.5)).is_none());
+    }
+
+    #[test]
+    fn nodes_lists_parents_before_children() {
+        let tree = tree();
+        assert_eq!(tree.nodes(0).len(), 2);
+        let nodes = tree.nodes(1);
+        assert_eq!(nodes.iter().filter(|n| n.node_type == LEAF).count(), 2);
+        assert!(nodes.iter().all(|n| n.node_type != LEAF || n.level == 1));
+        assert!(nodes.iter().take(2).all(|n| n.node_type == PARENT && n.level == 0));
+    }
+
+    #[test]
+    fn raycast_hits_the_first_leaf() {
+        let tree = tree();
+
+        // skips the empty node between the leaves
+        let (node, t) = tree.raycast(Vector3::new(-1.0, 0.1, 0.1), Vector3::new(1.0, 0.0, 0.0)).unwrap();
+        assert_eq!(node.value, 5);
+        assert_close(t, 1.0);
+
+        let (node, t) = tree.raycast(Vector3::new(0.3, 0.1, 0.1), Vector3::new(1.0, 0.0, 0.0)).unwrap();
+        assert_eq!(node.value, 7);
+        assert_close(t, 0.2);
+
+        let (node, t) = tree.raycast(Vector3::new(2.0, 0.1, 0.1), Vector3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert_eq!(node.value, 7);
+        assert_close(t, 1.25);
+
+        assert!(tree.raycast(Vector3::new(-1.0, 0.9, 0.1), Vector3::new(1.0, 0.0, 0.0)).is_none());
+        assert!(tree.raycast(Vector3::new(-1.0, 0.1, 0.1), Vector3::new(-1.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn face_normal_points_out_of_the_hit_face() {
+        let tree = tree();
+        let origin = Vector3::new(0.6, 2.0, 0.1);
+        let direction = Vector3::new(0.0, -1.0, 0.0);
+        let (node, t) = tree.raycast(origin, direction).unwrap();
+        assert_eq!(node.value, 7);
+        assert_eq!(node.face_normal(origin + direction * t), Vector3::new(0.0, 1.0, 0.0));
+
+        let (node, t) = tree.raycast(Vector3::new(2.0, 0.1, 0.1), Vector3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert_eq!(node.face_normal(Vector3::new(2.0 - t, 0.1, 0.1)), Vector3::new(1.0, 0.0, 0.0));
+    }
+}
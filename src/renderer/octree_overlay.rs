@@ -0,0 +1,217 @@
+use cgmath::Vector3;
+
+use super::{camera::{Camera, Projection}, octree::{NodeInfo, Octree, node_type_name}, program::Program, vao::{VertexArrayObject, VertexAttributePointer}, vbo::VertexBufferObject};
+use crate::resources::Resources;
+
+// the 12 edges of a box as pairs of corners, corners are indexed by their x, y and z bits
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1), (2, 3), (4, 5), (6, 7),
+    (0, 2), (1, 3), (4, 6), (5, 7),
+    (0, 4), (1, 5), (2, 6), (3, 7),
+];
+
+const HIGHLIGHT_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+struct LineBuffer {
+    vao: VertexArrayObject,
+    vbo: VertexBufferObject,
+}
+
+impl LineBuffer {
+    fn new() -> LineBuffer {
+        let vbo = VertexBufferObject::new::<f32>(Vec::new(), gl::ARRAY_BUFFER, gl::DYNAMIC_DRAW);
+        let pos = VertexAttributePointer {
+            location: 0,
+            size: 3,
+            offset: 0
+        };
+        let color = VertexAttributePointer {
+            location: 1,
+            size: 3,
+            offset: 3
+        };
+        let vao = VertexArrayObject::new::<f32>(vec![pos, color], vbo.id(), gl::FLOAT);
+        LineBuffer { vao, vbo }
+    }
+
+    fn draw(&self) {
+        let vertex_count = self.vbo.length() / 6;
+        if vertex_count == 0 {
+            return;
+        }
+        self.vao.bind();
+        unsafe {
+            gl::DrawArrays(gl::LINES, 0, vertex_count);
+        }
+        VertexArrayObject::unbind();
+    }
+}
+
+/// Draws the bounds of the octree nodes as lines over the raytraced image, colored by depth.
+/// Only works with the perspective projection
+pub struct OctreeOverlay {
+    program: Program,
+    nodes: LineBuffer,
+    highlight: LineBuffer,
+    // deepest level that is drawn
+    max_level: i32,
+    // the node lines are rebuilt before the next draw
+    dirty: bool,
+}
+
+impl OctreeOverlay {
+    pub fn from_resources(res: &Resources, max_level: i32) -> Result<OctreeOverlay, String> {
+        Ok(OctreeOverlay {
+            program: Program::from_resources(res, "shaders/octree_lines")?,
+            nodes: LineBuffer::new(),
+            highlight: LineBuffer::new(),
+            max_level,
+            dirty: true,
+        })
+    }
+
+    /// Should be called when the octree changes
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn max_level(&self) -> i32 {
+        self.max_level
+    }
+
+    pub fn set_max_level(&mut self, max_level: i32) {
+        if max_level != self.max_level {
+            self.max_level = max_level;
+            self.dirty = true;
+        }
+    }
+
+    /// Draw node bounds down to the max level, and the highlighted node in white
+    pub fn draw(&mut self, octree: &Octree, camera: &Camera, highlight: Option<&NodeInfo>) {
+        if camera.settings.projection != Projection::Perspective {
+            return;
+        }
+
+        if self.dirty {
+            let mut vertices = Vec::<f32>::new();
+            let max_level = self.max_level.max(0);
+            for node in octree.host.nodes(max_level) {
+                let t = node.level as f32 / max_level.max(1) as f32;
+                push_box(&mut vertices, octree, &node, heatmap(t));
+            }
+            self.nodes.vbo.update(&vertices, gl::DYNAMIC_DRAW);
+            self.dirty = false;
+        }
+
+        let mut highlight_vertices = Vec::<f32>::with_capacity(BOX_EDGES.len() * 2 * 6);
+        if let Some(node) = highlight {
+            push_box(&mut highlight_vertices, octree, node, HIGHLIGHT_COLOR);
+        }
+        self.highlight.vbo.update(&highlight_vertices, gl::DYNAMIC_DRAW);
+
+        let uniforms = [
+            ("origin", camera.origin),
+            ("horizontal", camera.horizontal),
+            ("vertical", camera.vertical),
+            ("lower_left_corner", camera.lower_left_corner),
+        ];
+        for (name, value) in uniforms.iter() {
+            if let Err(e) = self.program.set_vector3_f32(name, *value) {
+                eprintln!("{}", e);
+            }
+        }
+
+        self.program.bind();
+        self.nodes.draw();
+        self.highlight.draw();
+        Program::unbind();
+    }
+}
+
+/// Leaf under the crosshair in the screen center and the hit point in voxels at the deepest level, the
+/// octree is in unit space on the host
+pub fn crosshair_hit(octree: &Octree, camera: &Camera) -> Option<(NodeInfo, Vector3<f32>)> {
+    let center = camera.lower_left_corner + camera.horizontal * 0.5 + camera.vertical * 0.5;
+    let origin = (camera.origin - octree.min_point()) / octree.scale();
+    let direction = center - camera.origin;
+    octree.host.raycast(origin, direction).map(|(node, t)| {
+        // nudged into the hit voxel along the face normal by 0.01 voxels like ProceduralAlbedo in the shader
+        let point = origin + direction * t;
+        (node, point * octree.host.resolution() as f32 - node.face_normal(point) * 0.01)
+    })
+}
+
+/// Crosshair in the screen center
+pub fn show_crosshair(ctx: &egui::Context) {
+    let center = ctx.content_rect().center();
+    let stroke = egui::Stroke::new(1.0, egui::Color32::WHITE);
+    let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("crosshair")));
+    painter.line_segment([center - egui::vec2(6.0, 0.0), center + egui::vec2(6.0, 0.0)], stroke);
+    painter.line_segment([center - egui::vec2(0.0, 6.0), center + egui::vec2(0.0, 6.0)], stroke);
+}
+
+/// Window with the indirect cell of the node under the crosshair, and a crosshair in the screen center.
+/// albedo is the material color at the crosshair, computed on the cpu
+pub fn show_node_info(ctx: &egui::Context, node: Option<&NodeInfo>, albedo: Option<Vector3<f32>>, max_level: i32) {
+    show_crosshair(ctx);
+
+    egui::Window::new("Octree")
+        .default_pos(egui::pos2(8.0, 240.0))
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!("showing levels 0 - {}", max_level));
+            match node {
+                Some(node) => {
+                    egui::Grid::new("octree_node").show(ui, |ui| {
+                        ui.label("indirect cell");
+                        ui.monospace(format!("{}", node.cell));
+                        ui.end_row();
+                        ui.label("child");
+                        ui.monospace(format!("{}", node.child));
+                        ui.end_row();
+                        ui.label("type");
+                        ui.monospace(node_type_name(node.node_type));
+                        ui.end_row();
+                        ui.label("value");
+                        ui.monospace(format!("{}", node.value));
+                        ui.end_row();
+                        ui.label("level");
+                        ui.monospace(format!("{}", node.level));
+                        ui.end_row();
+                        if let Some(albedo) = albedo {
+                            ui.label("albedo");
+                            ui.horizontal(|ui| {
+                                let rgb = albedo.map(|c| (c.clamp(0.0, 1.0).sqrt() * 255.0) as u8);
+                                let (rect, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+                                ui.painter().rect_filled(rect, 0.0, egui::Color32::from_rgb(rgb.x, rgb.y, rgb.z));
+                                ui.monospace(format!("{:.2} {:.2} {:.2}", albedo.x, albedo.y, albedo.z));
+                            });
+                            ui.end_row();
+                        }
+                    });
+                },
+                None => {
+                    ui.label("no leaf under the crosshair");
+                },
+            }
+        });
+}
+
+fn push_box(vertices: &mut Vec<f32>, octree: &Octree, node: &NodeInfo, color: [f32; 3]) {
+    let min = octree.min_point() + node.min * octree.scale();
+    let size = node.size * octree.scale();
+    let corner = |i: usize| min + Vector3::new((i >> 2 & 1) as f32, (i >> 1 & 1) as f32, (i & 1) as f32) * size;
+    for (a, b) in BOX_EDGES.iter() {
+        for point in [corner(*a), corner(*b)].iter() {
+            vertices.extend_from_slice(&[point.x, point.y, point.z]);
+            vertices.extend_from_slice(&color);
+        }
+    }
+}
+
+// Same colors as Heatmap in raytracer.comp, blue for 0 through green to red for 1
+fn heatmap(t: f32) -> [f32; 3] {
+    let t = t.clamp(0.0, 1.0);
+    let channel = |offset: f32| (1.5 - (4.0 * t - offset).abs()).clamp(0.0, 1.0);
+    [channel(3.0), channel(2.0), channel(1.0)]
+}
@@ -0,0 +1,188 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector2, Vector3};
+use serde::{Serialize, Deserialize};
+
+/// Solid texture evaluated at the hit point, the result replaces the albedo of the material.
+/// Scales are the size of the pattern in voxels at the deepest level.
+///
+/// This is a port of ProceduralAlbedo in raytracer.comp, which is the only renderer. The port is used to show the
+/// color under the crosshair and must be kept in sync with the shader. The hashes match exactly, the floating point
+/// results can differ in the last bits
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum Procedural {
+    // alternates between the albedo and color
+    Checker { scale: f32, color: (f32, f32, f32) },
+    // fractal perlin noise from the albedo to color
+    Noise { scale: f32, color: (f32, f32, f32), octaves: u32 },
+    // veins of color along x, turbulence bends them with noise
+    Marble { scale: f32, color: (f32, f32, f32), turbulence: f32 },
+    // rings around the y axis, turbulence makes them uneven
+    Wood { scale: f32, color: (f32, f32, f32), turbulence: f32 },
+    // darkens or brightens each voxel by up to amount
+    RandomTint { amount: f32 },
+}
+
+// Must match the PROC_* constants in raytracer.comp
+const PROC_CHECKER: f32 = 1.0;
+const PROC_NOISE: f32 = 2.0;
+const PROC_MARBLE: f32 = 3.0;
+const PROC_WOOD: f32 = 4.0;
+const PROC_RANDOM_TINT: f32 = 5.0;
+/// Floats of a Procedural in raytracer.comp
+pub const PROCEDURAL_FLOATS: usize = 6;
+
+impl Procedural {
+    /// |Type |Scale |Param |Color rgb |, as uploaded to the Procedural storage buffer
+    pub fn to_floats(self) -> [f32; PROCEDURAL_FLOATS] {
+        match self {
+            Procedural::Checker { scale, color } => [PROC_CHECKER, scale, 0.0, color.0, color.1, color.2],
+            Procedural::Noise { scale, color, octaves } => [PROC_NOISE, scale, octaves as f32, color.0, color.1, color.2],
+            Procedural::Marble { scale, color, turbulence } => [PROC_MARBLE, scale, turbulence, color.0, color.1, color.2],
+            Procedural::Wood { scale, color, turbulence } => [PROC_WOOD, scale, turbulence, color.0, color.1, color.2],
+            Procedural::RandomTint { amount } => [PROC_RANDOM_TINT, 1.0, amount, 0.0, 0.0, 0.0],
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let [_, scale, _, r, g, b] = self.to_floats();
+        if scale <= 0.0 {
+            return Err(format!("procedural scale {} must be above 0", scale));
+        }
+        if [r, g, b].iter().any(|c| !(0.0..=1.0).contains(c)) {
+            return Err(format!("procedural color {:?} is outside of 0 - 1", (r, g, b)));
+        }
+        if let Procedural::RandomTint { amount } = self {
+            if !(0.0..=1.0).contains(amount) {
+                return Err(format!("random tint amount {} is outside of 0 - 1", amount));
+            }
+        }
+        Ok(())
+    }
+
+    /// Albedo at point, which is in voxels at the deepest level from the octree min point
+    pub fn albedo(&self, albedo: Vector3<f32>, point: Vector3<f32>) -> Vector3<f32> {
+        let [kind, scale, param, r, g, b] = self.to_floats();
+        let q = point / scale;
+        let color = Vector3::new(r, g, b);
+        let mix = |t: f32| albedo + (color - albedo) * t.clamp(0.0, 1.0);
+
+        if kind == PROC_CHECKER {
+            let sum = q.x.floor() as i32 + q.y.floor() as i32 + q.z.floor() as i32;
+            if sum & 1 == 0 { albedo } else { color }
+        } else if kind == PROC_NOISE {
+            mix(0.5 + 0.5 * fbm(q, (param as i32).max(1)))
+        } else if kind == PROC_MARBLE {
+            mix(0.5 + 0.5 * ((q.x + param * fbm(q, 4)) * PI).sin())
+        } else if kind == PROC_WOOD {
+            let ring = Vector2::new(q.x, q.z).magnitude() + param * perlin_noise(q);
+            let t = ring - ring.floor();
+            mix(t * t)
+        } else {
+            let cell = Vector3::new(q.x.floor() as i32, q.y.floor() as i32, q.z.floor() as i32);
+            let h = (hash(cell) & 0xffff) as f32 / 65535.0;
+            let tint = 1.0 + param * (2.0 * h - 1.0);
+            (albedo * tint).map(|c| c.clamp(0.0, 1.0))
+        }
+    }
+}
+
+// pcg3d, see ProceduralHash
+fn hash(cell: Vector3<i32>) -> u32 {
+    let mut v = cell.map(|c| (c as u32).wrapping_mul(1664525).wrapping_add(1013904223));
+    v.x = v.x.wrapping_add(v.y.wrapping_mul(v.z));
+    v.y = v.y.wrapping_add(v.z.wrapping_mul(v.x));
+    v.z = v.z.wrapping_add(v.x.wrapping_mul(v.y));
+    v = v.map(|c| c ^ (c >> 16));
+    v.x = v.x.wrapping_add(v.y.wrapping_mul(v.z));
+    v.y = v.y.wrapping_add(v.z.wrapping_mul(v.x));
+    v.z = v.z.wrapping_add(v.x.wrapping_mul(v.y));
+    v.x ^ v.y ^ v.z
+}
+
+// dot product of offset with one of the 12 cube edge gradients of improved perlin noise
+fn gradient_dot(cell: Vector3<i32>, offset: Vector3<f32>) -> f32 {
+    let h = hash(cell) % 12;
+    let u = if h < 8 { offset.x } else { offset.y };
+    let v = if h < 4 { offset.y } else { offset.z };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+fn perlin_noise(p: Vector3<f32>) -> f32 {
+    let floor = p.map(f32::floor);
+    let cell = floor.map(|c| c as i32);
+    let f = p - floor;
+    let w = f.map(|c| c * c * c * (c * (c * 6.0 - 15.0) + 10.0));
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+    let corner = |x: i32, y: i32, z: i32| {
+        gradient_dot(cell + Vector3::new(x, y, z), f - Vector3::new(x as f32, y as f32, z as f32))
+    };
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), w.x);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), w.x);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), w.x);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), w.x);
+    lerp(lerp(x00, x10, w.y), lerp(x01, x11, w.y), w.z)
+}
+
+fn fbm(p: Vector3<f32>, octaves: i32) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 0.5;
+    let mut norm = 0.0;
+    let mut p = p;
+    for _ in 0..octaves {
+        sum += amplitude * perlin_noise(p);
+        norm += amplitude;
+        p *= 2.0;
+        amplitude *= 0.5;
+    }
+    sum / norm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALBEDO: Vector3<f32> = Vector3::new(0.2, 0.4, 0.6);
+
+    #[test]
+    fn hash_matches_pcg3d() {
+        assert_eq!(hash(Vector3::new(0, 0, 0)), 0x0c521581);
+        assert_eq!(hash(Vector3::new(1, 2, 3)), 0xeb02d75b);
+        assert_eq!(hash(Vector3::new(-1, -1, -1)), 0x50fc3ba1);
+        assert_eq!(hash(Vector3::new(7, -3, 12)), 0x714de62e);
+    }
+
+    #[test]
+    fn checker_alternates_between_cells() {
+        let color = Vector3::new(1.0, 0.0, 0.0);
+        let checker = Procedural::Checker { scale: 1.0, color: (1.0, 0.0, 0.0) };
+        assert_eq!(checker.albedo(ALBEDO, Vector3::new(0.5, 0.5, 0.5)), ALBEDO);
+        assert_eq!(checker.albedo(ALBEDO, Vector3::new(1.5, 0.5, 0.5)), color);
+        assert_eq!(checker.albedo(ALBEDO, Vector3::new(1.5, 1.5, 0.5)), ALBEDO);
+        assert_eq!(checker.albedo(ALBEDO, Vector3::new(-0.5, 0.5, 0.5)), color);
+        assert_eq!(checker.albedo(ALBEDO, Vector3::new(-0.5, -0.5, 0.5)), ALBEDO);
+
+        let checker = Procedural::Checker { scale: 2.0, color: (1.0, 0.0, 0.0) };
+        assert_eq!(checker.albedo(ALBEDO, Vector3::new(1.5, 0.5, 0.5)), ALBEDO);
+        assert_eq!(checker.albedo(ALBEDO, Vector3::new(2.5, 0.5, 0.5)), color);
+    }
+
+    #[test]
+    fn random_tint_stays_in_range() {
+        let tint = Procedural::RandomTint { amount: 0.25 };
+        for i in -20..20 {
+            let point = Vector3::new(i as f32 + 0.5, (i * 7) as f32 + 0.5, (i * 13) as f32 + 0.5);
+            let albedo = tint.albedo(ALBEDO, point);
+            for axis in 0..3 {
+                assert!(albedo[axis] >= ALBEDO[axis] * 0.75 - 1e-6 && albedo[axis] <= ALBEDO[axis] * 1.25 + 1e-6);
+            }
+            // the same voxel has the same tint
+            assert_eq!(tint.albedo(ALBEDO, point + Vector3::new(0.4, 0.4, 0.4)), albedo);
+            let white = tint.albedo(Vector3::new(1.0, 1.0, 1.0), point);
+            assert!(white.x <= 1.0 && white.y <= 1.0 && white.z <= 1.0);
+        }
+        let none = Procedural::RandomTint { amount: 0.0 };
+        assert_eq!(none.albedo(ALBEDO, Vector3::new(3.5, 1.5, 8.5)), ALBEDO);
+    }
+}
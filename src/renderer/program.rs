@@ -57,7 +57,6 @@ impl Program {
         }
     }
 
-    #[allow(dead_code)]
     pub fn set_f32(&mut self, name: &str, value: f32) -> Result<(), InitializeErr> {
         match self.register_uniform(name) {
             Ok(()) => {
@@ -70,6 +69,18 @@ impl Program {
         }
     }
 
+    pub fn set_vector2_f32(&mut self, name: &str, value: cgmath::Vector2<f32>) -> Result<(), InitializeErr> {
+        match self.register_uniform(name) {
+            Ok(()) => {
+                unsafe {
+                    gl::ProgramUniform2f(self.id, self.uniforms[name], value.x, value.y);
+                }
+                Ok(())
+            }
+            Err(e) => Err(e.var_into_typed("vec2"))
+        }
+    }
+
     pub fn set_vector3_f32(&mut self, name: &str, value: cgmath::Vector3<f32>) -> Result<(), InitializeErr> {
         match self.register_uniform(name) {
             Ok(()) => {
@@ -0,0 +1,29 @@
+use serde::{Serialize, Deserialize};
+
+use super::{InitializeErr, program::Program};
+
+/// Shading that leaves the cubic geometry of the voxels as it is
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ShadingSettings {
+    // shade with normals estimated from the 3x3x3 neighbouring voxels instead of the face normals
+    pub smooth_normals: bool,
+    // how much the voxel ambient occlusion darkens corners in the debug views, 0 - 1
+    pub ao_strength: f32,
+}
+
+impl Default for ShadingSettings {
+    fn default() -> Self {
+        Self {
+            smooth_normals: false,
+            ao_strength: 0.8,
+        }
+    }
+}
+
+impl ShadingSettings {
+    pub fn apply(&self, program: &mut Program) -> Result<(), InitializeErr> {
+        program.set_i32("shading.smooth_normals", self.smooth_normals as i32)?;
+        program.set_f32("shading.ao_strength", self.ao_strength.clamp(0.0, 1.0))
+    }
+}
@@ -0,0 +1,128 @@
+use cgmath::Vector3;
+use serde::{Serialize, Deserialize};
+
+use super::{InitializeErr, camera::{Camera, Projection}, program::Program, texture::Texture};
+
+// image units in raytracer.comp
+const HISTORY_IN: u32 = 1;
+const HISTORY_OUT: u32 = 2;
+const GBUFFER_IN: u32 = 3;
+const GBUFFER_OUT: u32 = 4;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TemporalSettings {
+    pub enabled: bool,
+    // lowest weight of the new frame, higher values react faster but are noisier
+    pub min_blend: f32,
+    // history older than this many frames is not weighted more
+    pub max_history: f32,
+    // relative distance difference that still counts as the same surface
+    pub depth_tolerance: f32,
+    // lowest dot product between normals that still counts as the same surface
+    pub normal_tolerance: f32,
+}
+
+impl Default for TemporalSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_blend: 0.1,
+            max_history: 32.0,
+            depth_tolerance: 0.05,
+            normal_tolerance: 0.9,
+        }
+    }
+}
+
+// camera basis used to reproject into the previous frame
+#[derive(Clone, Copy)]
+struct CameraBasis {
+    origin: Vector3<f32>,
+    horizontal: Vector3<f32>,
+    vertical: Vector3<f32>,
+    lower_left_corner: Vector3<f32>,
+}
+
+/// Reuses radiance from earlier frames by reprojecting it with the previous camera and the per pixel hit distance.
+/// History and gbuffer textures are ping ponged so that the shader reads last frame while writing this frame
+pub struct TemporalAccumulation {
+    history: [Texture; 2],
+    gbuffer: [Texture; 2],
+    // index of the textures written this frame
+    current: usize,
+    frame: i32,
+    previous: Option<CameraBasis>,
+}
+
+impl TemporalAccumulation {
+    pub fn new(width: i32, height: i32) -> Result<TemporalAccumulation, InitializeErr> {
+        let texture = |active, slot| Texture::new_2d(active, slot, gl::RGBA32F, gl::RGBA, width, height);
+        Ok(TemporalAccumulation {
+            history: [texture(gl::TEXTURE1, HISTORY_IN)?, texture(gl::TEXTURE2, HISTORY_OUT)?],
+            gbuffer: [texture(gl::TEXTURE3, GBUFFER_IN)?, texture(gl::TEXTURE4, GBUFFER_OUT)?],
+            current: 1,
+            frame: 0,
+            previous: None,
+        })
+    }
+
+    /// Forget the history, e.g. when the scene changes in a way the reprojection can not detect
+    pub fn invalidate(&mut self) {
+        self.previous = None;
+    }
+
+    /// Bind textures and set uniforms for the next dispatch of the raytracer
+    pub fn prepare(&mut self, program: &mut Program, camera: &Camera) -> Result<(), InitializeErr> {
+        let (width, height) = (camera.render_texture.width(), camera.render_texture.height());
+        if self.history[0].width() != width || self.history[0].height() != height {
+            for texture in self.history.iter_mut().chain(self.gbuffer.iter_mut()) {
+                texture.resize_2d(width, height)?;
+            }
+            self.invalidate();
+        }
+
+        let settings = &camera.settings.temporal;
+        let enabled = settings.enabled && camera.settings.projection == Projection::Perspective;
+        if !enabled {
+            self.invalidate();
+        }
+
+        let previous = 1 - self.current;
+        self.history[previous].bind_image(HISTORY_IN);
+        self.history[self.current].bind_image(HISTORY_OUT);
+        self.gbuffer[previous].bind_image(GBUFFER_IN);
+        self.gbuffer[self.current].bind_image(GBUFFER_OUT);
+
+        // TODO: don't unwrap ... (send error to caller)
+        program.set_i32("temporal.enabled", enabled as i32).unwrap();
+        program.set_i32("temporal.history_valid", self.previous.is_some() as i32).unwrap();
+        // static noise is better than flickering noise without accumulation
+        program.set_i32("temporal.frame", if enabled { self.frame } else { 0 }).unwrap();
+        program.set_f32("temporal.min_blend", settings.min_blend.clamp(0.0, 1.0)).unwrap();
+        program.set_f32("temporal.max_history", settings.max_history.max(1.0)).unwrap();
+        program.set_f32("temporal.depth_tolerance", settings.depth_tolerance).unwrap();
+        program.set_f32("temporal.normal_tolerance", settings.normal_tolerance).unwrap();
+        if let Some(basis) = self.previous {
+            program.set_vector3_f32("temporal.previous_origin", basis.origin).unwrap();
+            program.set_vector3_f32("temporal.previous_horizontal", basis.horizontal).unwrap();
+            program.set_vector3_f32("temporal.previous_vertical", basis.vertical).unwrap();
+            program.set_vector3_f32("temporal.previous_lower_left_corner", basis.lower_left_corner).unwrap();
+        }
+        Ok(())
+    }
+
+    /// Remember the camera of the dispatched frame and swap textures
+    pub fn finish(&mut self, camera: &Camera) {
+        if camera.settings.temporal.enabled {
+            self.previous = Some(CameraBasis {
+                origin: camera.origin,
+                horizontal: camera.horizontal,
+                vertical: camera.vertical,
+                lower_left_corner: camera.lower_left_corner,
+            });
+        }
+        self.current = 1 - self.current;
+        self.frame = self.frame.wrapping_add(1);
+    }
+}
@@ -11,10 +11,12 @@ pub struct Texture {
     height: i32,
     depth: i32,
     target: GLenum,
+    // kept to reallocate the texture on resize, sampled textures are not bound to an image unit
+    bind_slot: Option<GLuint>,
+    internal_format: GLenum,
+    format: GLenum,
 }
 
-// TODO: impl Drop glDeleteTextures 
-
 impl Texture {
     pub fn width(&self) -> i32 {
         self.width
@@ -44,34 +46,128 @@ impl Texture {
         }
     }
 
+    /// Read back texel data as rgba floats, rows are bottom to top
+    pub fn read_rgba_f32(&self) -> Vec<f32> {
+        let mut pixels = vec![0.0f32; (self.width * self.height * self.depth * 4) as usize];
+        unsafe {
+            // make sure compute shader writes are visible
+            gl::MemoryBarrier(gl::TEXTURE_UPDATE_BARRIER_BIT);
+            gl::ActiveTexture(self.active);
+            gl::BindTexture(self.target, self.id);
+            gl::GetTexImage(self.target, 0, gl::RGBA, gl::FLOAT, pixels.as_mut_ptr() as *mut gl::types::GLvoid);
+        }
+        pixels
+    }
+
     pub fn new_2d(active: GLenum, bind_slot: GLuint, internal_format: GLenum, format: GLenum, width: GLsizei, height: GLsizei) -> Result<Self, InitializeErr> {       
         let target = gl::TEXTURE_2D;
         let id = prep_texture(active, target)?;
+        let mut texture = Texture {
+            id,
+            active,
+            width,
+            height,
+            depth: 1,
+            target,
+            bind_slot: Some(bind_slot),
+            internal_format,
+            format,
+        };
+        texture.allocate_2d(None)?;
+
+        Ok(texture)
+    }
+
+    /// Create a rgba8 texture that is only sampled in shaders, filter is used for both min and mag filtering
+    pub fn new_sampled_2d(active: GLenum, width: GLsizei, height: GLsizei, rgba: &[u8], filter: GLenum) -> Result<Self, InitializeErr> {
+        let target = gl::TEXTURE_2D;
+        let id = prep_texture(active, target)?;
+        let mut texture = Texture {
+            id,
+            active,
+            width,
+            height,
+            depth: 1,
+            target,
+            bind_slot: None,
+            internal_format: gl::RGBA8,
+            format: gl::RGBA,
+        };
+        unsafe {
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, filter as i32);
+            gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, filter as i32);
+        }
+        texture.allocate_2d(Some(rgba))?;
+
+        Ok(texture)
+    }
+
+    /// Overwrite a region of a rgba8 texture, rows are top to bottom as in the data
+    pub fn write_2d(&self, x: GLsizei, y: GLsizei, width: GLsizei, height: GLsizei, rgba: &[u8]) -> Result<(), InitializeErr> {
+        unsafe {
+            gl::ActiveTexture(self.active);
+            gl::BindTexture(self.target, self.id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                self.target,
+                0,
+                x,
+                y,
+                width,
+                height,
+                self.format,
+                gl::UNSIGNED_BYTE,
+                rgba.as_ptr() as *const gl::types::GLvoid
+            );
+            check_for_gl_error()
+        }
+    }
+
+    /// Bind the texture to an image unit, new textures are bound to the bind slot they were created with
+    pub fn bind_image(&self, unit: GLuint) {
+        unsafe {
+            gl::BindImageTexture(unit, self.id, 0, gl::FALSE, 0, gl::READ_WRITE, self.internal_format);
+        }
+    }
+
+    /// Reallocate a 2D texture with a new size, the content is undefined after this
+    pub fn resize_2d(&mut self, width: GLsizei, height: GLsizei) -> Result<(), InitializeErr> {
+        self.width = width;
+        self.height = height;
+        unsafe {
+            gl::ActiveTexture(self.active);
+            gl::BindTexture(self.target, self.id);
+        }
+        self.allocate_2d(None)
+    }
+
+    fn allocate_2d(&mut self, data: Option<&[u8]>) -> Result<(), InitializeErr> {
+        let pixels = match data {
+            Some(d) => d.as_ptr() as *const gl::types::GLvoid,
+            None => std::ptr::null(),
+        };
         unsafe { 
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             gl::TexImage2D(
-                target, 
+                self.target, 
                 0, 
-                internal_format as i32, 
-                width,
-                height,
+                self.internal_format as i32, 
+                self.width,
+                self.height,
                 0, 
-                format, 
+                self.format, 
                 gl::UNSIGNED_BYTE, 
-                std::ptr::null() 
+                pixels
             );
             check_for_gl_error()?;
-            gl::BindImageTexture(bind_slot, id, 0, gl::FALSE, 0, gl::READ_WRITE, internal_format);
-            check_for_gl_error()?;
+            if let Some(slot) = self.bind_slot {
+                gl::BindImageTexture(slot, self.id, 0, gl::FALSE, 0, gl::READ_WRITE, self.internal_format);
+                check_for_gl_error()?;
+            }
         }
-
-        Ok(Texture {
-            id,
-            active,
-            width,
-            height,
-            depth: 1,
-            target,
-        })
+        Ok(())
     }
 }
 
@@ -90,4 +186,12 @@ fn prep_texture(active: GLenum, target: GLenum) -> Result<GLuint, InitializeErr>
     }
 
     Ok(id)
-}
\ No newline at end of file
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use super::{camera::CameraSettings, materials::MaterialLibrary, octree::Octree};
+
+fn swatch(ui: &mut egui::Ui, albedo: (f32, f32, f32)) {
+    // the albedo is linear, the ui is drawn in srgb
+    let srgb = |c: f32| (c.clamp(0.0, 1.0).sqrt() * 255.0) as u8;
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(14.0, 14.0), egui::Sense::hover());
+    ui.painter().rect_filled(rect, 2.0, egui::Color32::from_rgb(srgb(albedo.0), srgb(albedo.1), srgb(albedo.2)));
+}
+
+/// Window with every material and its albedo, clicking a material selects it for placing
+pub fn show_palette(ctx: &egui::Context, library: &MaterialLibrary, active_voxel: &mut u32) {
+    egui::Window::new("Palette")
+        .default_pos(egui::pos2(260.0, 8.0))
+        .default_height(320.0)
+        .show(ctx, |ui| {
+            match library.materials.get(*active_voxel as usize) {
+                Some(material) => {
+                    ui.horizontal(|ui| {
+                        ui.label("placing");
+                        swatch(ui, material.albedo);
+                        ui.strong(&material.name);
+                    });
+                },
+                None => {
+                    ui.label("no material is selected");
+                },
+            }
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (i, material) in library.materials.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        swatch(ui, material.albedo);
+                        if ui.selectable_label(i as u32 == *active_voxel, format!("{} {}", i, material.name)).clicked() {
+                            *active_voxel = i as u32;
+                        }
+                    });
+                }
+            });
+        });
+}
+
+/// Window with sliders for the camera settings, returns true when a setting changed
+pub fn show_camera_settings(ctx: &egui::Context, settings: &mut CameraSettings) -> bool {
+    let mut changed = false;
+    egui::Window::new("Camera")
+        .default_pos(egui::pos2(500.0, 8.0))
+        .resizable(false)
+        .show(ctx, |ui| {
+            let mut add = |ui: &mut egui::Ui, widget: egui::Slider| changed |= ui.add(widget).changed();
+            add(ui, egui::Slider::new(&mut settings.samples_per_pixel, 1..=64).text("samples per pixel"));
+            add(ui, egui::Slider::new(&mut settings.max_bounce, 1..=32).text("max bounce"));
+            add(ui, egui::Slider::new(&mut settings.render_scale, 0.25..=1.0).text("render scale"));
+            add(ui, egui::Slider::new(&mut settings.normal_speed, 0.001..=1.0).logarithmic(true).text("speed"));
+            add(ui, egui::Slider::new(&mut settings.sprint_speed, 0.001..=1.0).logarithmic(true).text("sprint speed"));
+            add(ui, egui::Slider::new(&mut settings.turn_rate, 0.001..=0.5).logarithmic(true).text("turn rate"));
+            changed |= ui.checkbox(&mut settings.temporal.enabled, "temporal accumulation").changed();
+            changed |= ui.checkbox(&mut settings.fog.enabled, "fog").changed();
+            changed |= ui.checkbox(&mut settings.shading.smooth_normals, "smooth normals").changed();
+        });
+    changed
+}
+
+/// Window with the frame rate and the size of the octree
+pub fn show_stats(ctx: &egui::Context, fps: Option<f32>, octree: &Octree) {
+    egui::Window::new("Stats")
+        .default_pos(egui::pos2(500.0, 260.0))
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("stats").show(ui, |ui| {
+                ui.label("fps");
+                ui.monospace(fps.map_or("-".to_string(), |fps| format!("{:.1}", fps)));
+                ui.end_row();
+                ui.label("max depth");
+                ui.monospace(format!("{}", octree.host.max_depth()));
+                ui.end_row();
+                ui.label("resolution");
+                ui.monospace(format!("{0}x{0}x{0}", octree.host.resolution()));
+                ui.end_row();
+                ui.label("cells");
+                ui.monospace(format!("{} / {}", octree.host.active_cell_count(), octree.cell_count()));
+                ui.end_row();
+                ui.label("leaves");
+                ui.monospace(format!("{}", octree.host.leaf_count()));
+                ui.end_row();
+            });
+        });
+}
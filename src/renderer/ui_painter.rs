@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use cgmath::Vector2;
+use egui::{ClippedPrimitive, ImageData, TextureFilter, TextureId, TexturesDelta, epaint::Primitive};
+
+use super::{program::Program, texture::Texture, vao::{VertexArrayObject, VertexAttributePointer}, vbo::VertexBufferObject};
+use crate::resources::Resources;
+
+// texture unit used by ui textures, 0 is the render texture and 1 - 4 are used by temporal accumulation
+const UI_TEXTURE_UNIT: u32 = 5;
+
+/// Draws tessellated egui output on top of the current framebuffer
+pub struct UiPainter {
+    program: Program,
+    vao: VertexArrayObject,
+    vertices: VertexBufferObject,
+    indices: VertexBufferObject,
+    textures: HashMap<TextureId, Texture>,
+}
+
+impl UiPainter {
+    pub fn from_resources(res: &Resources) -> Result<UiPainter, String> {
+        let mut program = Program::from_resources(res, "shaders/ui")?;
+        program.set_i32("ui_texture", UI_TEXTURE_UNIT as i32).map_err(|e| format!("{}", e))?;
+
+        let vertices = VertexBufferObject::new::<f32>(Vec::new(), gl::ARRAY_BUFFER, gl::STREAM_DRAW);
+        let indices = VertexBufferObject::new::<u32>(Vec::new(), gl::ELEMENT_ARRAY_BUFFER, gl::STREAM_DRAW);
+        let vao = {
+            let pos = VertexAttributePointer {
+                location: 0,
+                size: 2,
+                offset: 0
+            };
+            let uv = VertexAttributePointer {
+                location: 1,
+                size: 2,
+                offset: 2
+            };
+            let color = VertexAttributePointer {
+                location: 2,
+                size: 4,
+                offset: 4
+            };
+            VertexArrayObject::new::<f32>(vec![pos, uv, color], vertices.id(), gl::FLOAT)
+        };
+
+        Ok(UiPainter {
+            program,
+            vao,
+            vertices,
+            indices,
+            textures: HashMap::new(),
+        })
+    }
+
+    /// Draw primitives to a framebuffer of screen_size pixels. Texture changes are applied before drawing
+    /// and textures are freed after drawing
+    pub fn paint(&mut self, screen_size: (i32, i32), pixels_per_point: f32, primitives: &[ClippedPrimitive], textures_delta: &TexturesDelta) {
+        for (id, delta) in textures_delta.set.iter() {
+            if let Err(e) = self.set_texture(*id, delta) {
+                eprintln!("failed to upload ui texture: {}", e);
+            }
+        }
+
+        let points = Vector2::new(screen_size.0 as f32 / pixels_per_point, screen_size.1 as f32 / pixels_per_point);
+        self.program.set_vector2_f32("screen_size", points).unwrap();
+
+        self.program.bind();
+        self.vao.bind();
+        unsafe {
+            gl::Enable(gl::BLEND);
+            // egui colors are premultiplied
+            gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+            gl::Enable(gl::SCISSOR_TEST);
+        }
+
+        let mut vertex_data = Vec::<f32>::new();
+        for primitive in primitives {
+            let mesh = match &primitive.primitive {
+                Primitive::Mesh(mesh) => mesh,
+                Primitive::Callback(_) => continue,
+            };
+            let texture = match self.textures.get(&mesh.texture_id) {
+                Some(t) => t,
+                None => continue,
+            };
+
+            // clip rect in points to a scissor box in pixels with origin in the bottom left corner
+            let clip = primitive.clip_rect;
+            let min_x = (clip.min.x * pixels_per_point).round().max(0.0) as i32;
+            let min_y = (clip.min.y * pixels_per_point).round().max(0.0) as i32;
+            let max_x = ((clip.max.x * pixels_per_point).round() as i32).min(screen_size.0);
+            let max_y = ((clip.max.y * pixels_per_point).round() as i32).min(screen_size.1);
+            if max_x <= min_x || max_y <= min_y {
+                continue;
+            }
+
+            vertex_data.clear();
+            for vertex in mesh.vertices.iter() {
+                vertex_data.extend_from_slice(&[vertex.pos.x, vertex.pos.y, vertex.uv.x, vertex.uv.y]);
+                vertex_data.extend_from_slice(&vertex.color.to_normalized_gamma_f32());
+            }
+            self.vertices.update(&vertex_data, gl::STREAM_DRAW);
+            self.indices.update(&mesh.indices, gl::STREAM_DRAW);
+
+            texture.bind();
+            self.indices.bind();
+            unsafe {
+                gl::Scissor(min_x, screen_size.1 - max_y, max_x - min_x, max_y - min_y);
+                gl::DrawElements(gl::TRIANGLES, self.indices.length(), gl::UNSIGNED_INT, std::ptr::null());
+            }
+            self.indices.unbind();
+        }
+
+        unsafe {
+            gl::Disable(gl::SCISSOR_TEST);
+            gl::Disable(gl::BLEND);
+        }
+        VertexArrayObject::unbind();
+        Program::unbind();
+
+        for id in textures_delta.free.iter() {
+            self.textures.remove(id);
+        }
+    }
+
+    fn set_texture(&mut self, id: TextureId, delta: &egui::epaint::ImageDelta) -> Result<(), String> {
+        let ImageData::Color(image) = &delta.image;
+        let rgba: Vec<u8> = image.pixels.iter().flat_map(|p| p.to_array()).collect();
+        let (width, height) = (image.size[0] as i32, image.size[1] as i32);
+
+        match (delta.pos, self.textures.get(&id)) {
+            (Some([x, y]), Some(texture)) => {
+                texture.write_2d(x as i32, y as i32, width, height, &rgba).map_err(|e| format!("{}", e))
+            },
+            _ => {
+                let filter = match delta.options.magnification {
+                    TextureFilter::Nearest => gl::NEAREST,
+                    TextureFilter::Linear => gl::LINEAR,
+                };
+                let texture = Texture::new_sampled_2d(gl::TEXTURE0 + UI_TEXTURE_UNIT, width, height, &rgba, filter)
+                    .map_err(|e| format!("{}", e))?;
+                self.textures.insert(id, texture);
+                Ok(())
+            }
+        }
+    }
+}
@@ -53,4 +53,19 @@ impl VertexBufferObject {
             binding
         }
     }
-}
\ No newline at end of file
+
+    /// Replace the content of the buffer, the buffer storage is reallocated
+    pub fn update<T>(&mut self, data: &[T], usage: GLenum) {
+        self.length = data.len() as i32;
+        unsafe {
+            gl::BindBuffer(self.binding, self.id);
+            gl::BufferData(
+                self.binding,
+                std::mem::size_of_val(data) as gl::types::GLsizeiptr,
+                data.as_ptr() as *const gl::types::GLvoid,
+                usage,
+            );
+            gl::BindBuffer(self.binding, 0);
+        }
+    }
+}
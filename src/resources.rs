@@ -39,6 +39,11 @@ impl Resources {
         })
     }
 
+    /// Creates a path from a resources folder anywhere on disk
+    pub fn from_path(root_path: &Path) -> Resources {
+        Resources { root_path: root_path.to_path_buf() }
+    }
+
     pub fn to_abs_path(&self, rel_path: &str) -> PathBuf {
         resource_name_to_path(&self.root_path, rel_path)
     }
@@ -57,6 +62,11 @@ impl Resources {
         Ok(buffer)
     }
 
+    pub fn write_buffer(&self, resource_name: &str, buffer: &[u8]) -> Result<(), Error> {
+        fs::write(resource_name_to_path(&self.root_path, resource_name), buffer)?;
+        Ok(())
+    }
+
     pub fn load_cstring(&self, resource_name: &str) -> Result<ffi::CString, Error> {
         let buffer = self.load_buffer(resource_name)?;
 
@@ -68,7 +78,6 @@ impl Resources {
         Ok(unsafe { ffi::CString::from_vec_unchecked(buffer) })
     }
 
-    #[allow(dead_code)]
     pub fn load_image(&self, resource_name: &str) -> ImageResult<DynamicImage> {
         // TODO: validate extension name to be supported format
 
@@ -0,0 +1,28 @@
+use glutin::event::VirtualKeyCode;
+
+/// Events sent from the event loop (and file watchers) to the render thread.
+/// They are consumed in order once per frame so nothing that happens between frames is lost
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AppEvent {
+    // pressed is also sent for key repeats
+    KeyPressed(VirtualKeyCode),
+    KeyReleased(VirtualKeyCode),
+    // mouse button ids as reported by device events
+    ButtonPressed(u32),
+    ButtonReleased(u32),
+    // mouse movement in pixels
+    MouseMoved(f32, f32),
+    // cursor position in physical pixels from the top left of the window
+    CursorMoved(f32, f32),
+    // scroll steps, positive values are scrolls away from the user
+    Scrolled(f32),
+    // new inner size of the window in physical pixels
+    Resized(u32, u32),
+    // the window lost focus, held keys and buttons should be released
+    FocusLost,
+    CameraSettingsChanged,
+    InputBindingsChanged,
+    MaterialsChanged,
+    // the render thread stores state and stops
+    Exit,
+}
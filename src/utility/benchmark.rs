@@ -0,0 +1,217 @@
+use std::{fs, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Serialize, Deserialize};
+
+use crate::{renderer::{camera::{CameraPose, Upscale}, fog::FogSettings, gpu_timer::GpuPass, octree::Octree, shading::ShadingSettings, temporal::TemporalSettings}, resources::Resources};
+
+use super::camera_path::CameraPath;
+
+/// A reproducible benchmark run, loaded from benchmarks/<name>.ron. The settings that change the rendered image are
+/// part of the scene, the rest is taken from camera.ron
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BenchmarkScene {
+    // measured frames, the path is played over exactly this many frames
+    pub frames: u32,
+    // frames rendered at the first pose before measuring starts
+    #[serde(default = "default_warmup_frames")]
+    pub warmup_frames: u32,
+    pub seed: i32,
+    pub samples_per_pixel: i32,
+    pub max_bounce: i32,
+    // render resolution, independent of the window size
+    pub resolution: (i32, i32),
+    // rendering features, missing ones get the defaults of the settings and not the values in camera.ron
+    #[serde(default)]
+    pub temporal: TemporalSettings,
+    #[serde(default)]
+    pub fog: FogSettings,
+    #[serde(default)]
+    pub shading: ShadingSettings,
+    #[serde(default)]
+    pub upscale: Upscale,
+    pub path: CameraPath,
+}
+
+fn default_warmup_frames() -> u32 {
+    10
+}
+
+#[derive(Debug, Serialize)]
+pub struct Percentiles {
+    pub min: f32,
+    pub p50: f32,
+    pub p90: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+impl Percentiles {
+    /// Nearest rank percentiles, none for no samples
+    pub fn from_samples(samples: &[f32]) -> Option<Percentiles> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let rank = |p: f32| sorted[((p / 100.0 * sorted.len() as f32).ceil() as usize).clamp(1, sorted.len()) - 1];
+
+        Some(Percentiles {
+            min: sorted[0],
+            p50: rank(50.0),
+            p90: rank(90.0),
+            p95: rank(95.0),
+            p99: rank(99.0),
+            max: sorted[sorted.len() - 1],
+            mean: sorted.iter().sum::<f32>() / sorted.len() as f32,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OctreeStats {
+    pub max_depth: i32,
+    pub active_cells: u32,
+    pub allocated_cells: i32,
+    pub leaves: usize,
+}
+
+impl OctreeStats {
+    pub fn from_octree(octree: &Octree) -> OctreeStats {
+        OctreeStats {
+            max_depth: octree.host.max_depth(),
+            active_cells: octree.host.active_cell_count(),
+            allocated_cells: octree.cell_count(),
+            leaves: octree.host.leaf_count(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchmarkReport {
+    pub scene: String,
+    pub frames: u32,
+    pub seed: i32,
+    pub samples_per_pixel: i32,
+    pub max_bounce: i32,
+    pub resolution: (i32, i32),
+    pub total_seconds: f32,
+    // camera rays only, bounces are not counted
+    pub primary_rays_per_second: f32,
+    pub frame_ms: Option<Percentiles>,
+    pub raytrace_gpu_ms: Option<Percentiles>,
+    pub octree: OctreeStats,
+}
+
+/// Plays a benchmark scene and records frame times
+pub struct Benchmark {
+    name: String,
+    scene: BenchmarkScene,
+    frame: u32,
+    frame_ms: Vec<f32>,
+    // by measured frame, gpu timings arrive a few frames late and none for a frame whose timing was lost
+    raytrace_ms: Vec<Option<f32>>,
+}
+
+impl Benchmark {
+    pub fn from_resources(res: &Resources, name: &str) -> Result<Benchmark, String> {
+        let path = format!("benchmarks/{}.ron", name);
+        let bytes = res.load_buffer(&path)
+            .map_err(|e| format!("Error loading resource {}: {:?}", path, e))?;
+        let scene: BenchmarkScene = ron::de::from_bytes(&bytes[0..])
+            .map_err(|e| format!("Error parsing {}: {}", path, e))?;
+        if scene.frames == 0 || scene.path.keyframes.is_empty() {
+            return Err(format!("Benchmark {} needs at least one frame and one keyframe", path));
+        }
+
+        Ok(Benchmark {
+            name: name.to_string(),
+            frame_ms: Vec::with_capacity(scene.frames as usize),
+            raytrace_ms: vec![None; scene.frames as usize],
+            scene,
+            frame: 0,
+        })
+    }
+
+    pub fn scene(&self) -> &BenchmarkScene {
+        &self.scene
+    }
+
+    /// Camera pose for the current frame
+    pub fn pose(&self) -> Option<CameraPose> {
+        let measured = self.frame.saturating_sub(self.scene.warmup_frames);
+        let progress = measured as f32 / (self.scene.frames.max(2) - 1) as f32;
+        self.scene.path.sample(progress.min(1.0) * self.scene.path.duration())
+    }
+
+    /// Record the cpu time of the current frame and any gpu timings that became available, then move to the next frame
+    pub fn record_frame(&mut self, frame_ms: f32, gpu_timings: &[(usize, GpuPass, f32)]) {
+        if self.frame >= self.scene.warmup_frames {
+            self.frame_ms.push(frame_ms);
+        }
+        self.frame += 1;
+        self.record_gpu_timings(gpu_timings);
+    }
+
+    /// Record gpu timings that are read after the last frame. The benchmark starts with the gpu timer, so their
+    /// frame indices count the same frames
+    pub fn record_gpu_timings(&mut self, gpu_timings: &[(usize, GpuPass, f32)]) {
+        for (frame, _, ms) in gpu_timings.iter().filter(|(_, pass, _)| *pass == GpuPass::Raytrace) {
+            let measured = (*frame as u32).checked_sub(self.scene.warmup_frames);
+            if let Some(timing) = measured.and_then(|i| self.raytrace_ms.get_mut(i as usize)) {
+                *timing = Some(*ms);
+            }
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.frame >= self.scene.warmup_frames + self.scene.frames
+    }
+
+    fn report(&self, octree: &Octree) -> BenchmarkReport {
+        let total_seconds = self.frame_ms.iter().sum::<f32>() / 1000.0;
+        let (width, height) = self.scene.resolution;
+        let rays = width as f32 * height as f32 * self.scene.samples_per_pixel as f32 * self.frame_ms.len() as f32;
+
+        BenchmarkReport {
+            scene: self.name.clone(),
+            frames: self.scene.frames,
+            seed: self.scene.seed,
+            samples_per_pixel: self.scene.samples_per_pixel,
+            max_bounce: self.scene.max_bounce,
+            resolution: self.scene.resolution,
+            total_seconds,
+            primary_rays_per_second: if total_seconds > 0.0 { rays / total_seconds } else { 0.0 },
+            frame_ms: Percentiles::from_samples(&self.frame_ms),
+            raytrace_gpu_ms: Percentiles::from_samples(&self.raytrace_ms.iter().flatten().copied().collect::<Vec<f32>>()),
+            octree: OctreeStats::from_octree(octree),
+        }
+    }
+
+    /// Write the report as json and the per frame timings as csv to directory, returns the path of the json file
+    pub fn write_report(&self, directory: &Path, octree: &Octree) -> Result<PathBuf, String> {
+        fs::create_dir_all(directory)
+            .map_err(|e| format!("Failed to create benchmark directory {:?}: {}", directory, e))?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let json_path = directory.join(format!("{}_{}.json", self.name, timestamp));
+        let csv_path = json_path.with_extension("csv");
+
+        let json = serde_json::to_string_pretty(&self.report(octree))
+            .map_err(|e| format!("Failed to serialize benchmark report: {}", e))?;
+        fs::write(&json_path, json)
+            .map_err(|e| format!("Failed to write {:?}: {}", json_path, e))?;
+
+        let mut csv = String::from("frame,frame_ms,raytrace_gpu_ms\n");
+        for (i, frame_ms) in self.frame_ms.iter().enumerate() {
+            let raytrace = self.raytrace_ms[i].map_or(String::new(), |ms| format!("{:.4}", ms));
+            csv.push_str(&format!("{},{:.4},{}\n", i, frame_ms, raytrace));
+        }
+        fs::write(&csv_path, csv)
+            .map_err(|e| format!("Failed to write {:?}: {}", csv_path, e))?;
+
+        Ok(json_path)
+    }
+}
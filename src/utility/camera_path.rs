@@ -0,0 +1,110 @@
+use cgmath::VectorSpace;
+use serde::{Serialize, Deserialize};
+
+use crate::{renderer::camera::CameraPose, resources::Resources};
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct CameraKeyframe {
+    // seconds since the start of the path
+    pub time: f32,
+    pub pose: CameraPose,
+}
+
+/// Recorded camera keyframes that can be played back as a smooth flythrough
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CameraPath {
+    // frames per second used when playback is captured to images
+    #[serde(default = "default_capture_fps")]
+    pub capture_fps: f32,
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+fn default_capture_fps() -> f32 {
+    30.0
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        Self {
+            capture_fps: default_capture_fps(),
+            keyframes: Vec::new(),
+        }
+    }
+}
+
+impl CameraPath {
+    pub fn from_resources(res: &Resources, name: &str) -> Result<CameraPath, String> {
+        let bytes = res.load_buffer(name)
+            .map_err(|e| format!("Error loading resource {}: {:?}", name, e))?;
+        ron::de::from_bytes(&bytes[0..])
+            .map_err(|e| format!("Error parsing {}: {}", name, e))
+    }
+
+    pub fn save(&self, res: &Resources, name: &str) -> Result<(), String> {
+        let s = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| format!("{}", e))?;
+        res.write_buffer(name, s.as_bytes())
+            .map_err(|e| format!("Error writing resource {}: {:?}", name, e))
+    }
+
+    /// Append a keyframe, time is clamped so that keyframes stay ordered
+    pub fn push(&mut self, time: f32, pose: CameraPose) {
+        let time = self.keyframes.last().map_or(time, |k| time.max(k.time));
+        self.keyframes.push(CameraKeyframe { time, pose });
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    /// Interpolated pose at time. Position and fov use Catmull-Rom splines and orientation uses slerp
+    pub fn sample(&self, time: f32) -> Option<CameraPose> {
+        let last = self.keyframes.len().checked_sub(1)?;
+        let segment = self.keyframes
+            .iter()
+            .skip(1)
+            .position(|k| k.time > time)
+            .unwrap_or(last.saturating_sub(1));
+
+        let k1 = &self.keyframes[segment];
+        let k2 = &self.keyframes[(segment + 1).min(last)];
+        // end points are repeated to give the spline a tangent
+        let k0 = &self.keyframes[segment.saturating_sub(1)];
+        let k3 = &self.keyframes[(segment + 2).min(last)];
+
+        let segment_time = k2.time - k1.time;
+        let t = if segment_time > 0.0 {
+            ((time - k1.time) / segment_time).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        let mut pose = CameraPose {
+            origin: catmull_rom(k0.pose.origin, k1.pose.origin, k2.pose.origin, k3.pose.origin, t),
+            vertical_fov: catmull_rom_f32(k0.pose.vertical_fov, k1.pose.vertical_fov, k2.pose.vertical_fov, k3.pose.vertical_fov, t),
+            ..k1.pose
+        };
+        pose.set_orientation(k1.pose.orientation().slerp(k2.pose.orientation(), t));
+
+        Some(pose)
+    }
+}
+
+// Uniform Catmull-Rom spline between p1 and p2
+fn catmull_rom<V: VectorSpace<Scalar = f32>>(p0: V, p1: V, p2: V, p3: V, t: f32) -> V {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
+
+fn catmull_rom_f32(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3) * 0.5
+}
@@ -0,0 +1,113 @@
+use serde::{Serialize, Deserialize};
+
+/// Lowers samples per pixel and render scale when frames take too long, and raises them again when there is headroom
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DynamicResolutionSettings {
+    pub enabled: bool,
+    pub target_fps: f32,
+    // lowest render scale the controller is allowed to use, the render_scale setting is the highest
+    pub min_scale: f32,
+    // lower samples per pixel before lowering the resolution
+    pub adjust_samples: bool,
+    pub min_samples: i32,
+}
+
+impl Default for DynamicResolutionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_fps: 60.0,
+            min_scale: 0.25,
+            adjust_samples: true,
+            min_samples: 1,
+        }
+    }
+}
+
+pub struct DynamicResolution {
+    // smoothed frame time in seconds
+    frame_time: f32,
+    since_adjust: f32,
+    scale: f32,
+    samples: i32,
+}
+
+impl DynamicResolution {
+    pub fn new(scale: f32, samples: i32) -> DynamicResolution {
+        DynamicResolution {
+            frame_time: 0.0,
+            since_adjust: 0.0,
+            scale,
+            samples,
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn samples(&self) -> i32 {
+        self.samples
+    }
+
+    /// Feed the last frame time, returns true when scale or samples changed.
+    /// max_scale and max_samples are the configured quality that the controller never goes above
+    pub fn update(&mut self, settings: &DynamicResolutionSettings, max_scale: f32, max_samples: i32, delta_time: f32) -> bool {
+        // weight of the newest frame in the smoothed frame time
+        const SMOOTHING: f32 = 0.1;
+        // seconds between adjustments, the smoothed time needs a while to react to a change
+        const ADJUST_INTERVAL: f32 = 0.5;
+        // scale is changed in steps so the render texture is not reallocated for tiny changes
+        const SCALE_STEP: f32 = 0.05;
+        // frame time has to be this far from the target before anything changes
+        const SLOW: f32 = 0.9;
+        const FAST: f32 = 1.2;
+
+        if delta_time <= 0.0 {
+            return false;
+        }
+        self.frame_time = if self.frame_time == 0.0 {
+            delta_time
+        } else {
+            self.frame_time + (delta_time - self.frame_time) * SMOOTHING
+        };
+
+        self.since_adjust += delta_time;
+        if self.since_adjust < ADJUST_INTERVAL {
+            return false;
+        }
+        self.since_adjust = 0.0;
+
+        let min_scale = settings.min_scale.min(max_scale);
+        let min_samples = settings.min_samples.max(1).min(max_samples);
+        let (old_scale, old_samples) = (self.scale, self.samples);
+        // how much faster frames could be, the cost of a frame is roughly scale^2 * samples
+        let headroom = (1.0 / settings.target_fps.max(1.0)) / self.frame_time;
+        let quantize = |scale: f32| (scale / SCALE_STEP).round() * SCALE_STEP;
+        if headroom < SLOW {
+            if settings.adjust_samples && self.samples > min_samples {
+                let samples = (self.samples as f32 * headroom) as i32;
+                self.samples = samples.min(self.samples - 1).max(min_samples);
+            } else {
+                let scale = quantize(self.scale * headroom.sqrt());
+                self.scale = scale.min(self.scale - SCALE_STEP).max(min_scale);
+            }
+        } else if headroom > FAST {
+            // raise slowly to avoid oscillating around the target
+            if self.scale < max_scale {
+                let scale = quantize(self.scale * headroom.sqrt().min(1.25));
+                self.scale = scale.max(self.scale + SCALE_STEP).min(max_scale);
+            } else if self.samples < max_samples {
+                self.samples += 1;
+            }
+        }
+
+        let changed = self.scale != old_scale || self.samples != old_samples;
+        if changed {
+            // frame times measured at the old quality are not useful anymore
+            self.frame_time = 0.0;
+        }
+        changed
+    }
+}
@@ -0,0 +1,46 @@
+use std::{fs, path::PathBuf};
+
+use image::{ImageBuffer, Rgba};
+
+use crate::renderer::texture::Texture;
+
+/// Writes numbered png images of a texture, one per call to capture
+pub struct FrameCapture {
+    directory: PathBuf,
+    frame: u32,
+}
+
+impl FrameCapture {
+    pub fn new(directory: PathBuf) -> Result<FrameCapture, String> {
+        fs::create_dir_all(&directory)
+            .map_err(|e| format!("Failed to create capture directory {:?}: {}", directory, e))?;
+
+        Ok(FrameCapture {
+            directory,
+            frame: 0,
+        })
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn capture(&mut self, texture: &Texture) -> Result<(), String> {
+        let width = texture.width() as u32;
+        let height = texture.height() as u32;
+        let pixels = texture.read_rgba_f32();
+
+        // opengl stores the bottom row first
+        let image = ImageBuffer::from_fn(width, height, |x, y| {
+            let i = (((height - 1 - y) * width + x) * 4) as usize;
+            let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+            Rgba([to_u8(pixels[i]), to_u8(pixels[i + 1]), to_u8(pixels[i + 2]), 255])
+        });
+
+        let path = self.directory.join(format!("frame_{:05}.png", self.frame));
+        image.save(&path).map_err(|e| format!("Failed to save {:?}: {}", path, e))?;
+        self.frame += 1;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,428 @@
+use glutin::event::VirtualKeyCode;
+use serde::{Serialize, Deserialize};
+
+use crate::resources::Resources;
+
+use super::app_event::AppEvent;
+
+/// Things the user can do, independent of which key or button triggers them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    // also jumps in walk mode
+    MoveUp,
+    MoveDown,
+    Sprint,
+    RollLeft,
+    RollRight,
+    ZoomIn,
+    ZoomOut,
+    PlaceVoxel,
+    RemoveVoxel,
+    // material index used when placing voxels
+    SelectVoxel(u32),
+    // step through all materials, also those without a number key
+    NextVoxel,
+    PreviousVoxel,
+    JumpToBookmark(u8),
+    StoreBookmark(u8),
+    AddKeyframe,
+    ClearPath,
+    PlayPath,
+    CapturePath,
+    ToggleOrbit,
+    FocusOrbit,
+    ToggleTurntable,
+    ToggleWalk,
+    ToggleFullscreen,
+    // show frame and gpu pass timings
+    ToggleProfiler,
+    // cycle through the raytracer debug views
+    NextDebugView,
+    // shade with normals estimated from the neighbouring voxels
+    ToggleSmoothNormals,
+    // material editor window, the voxel under the crosshair is picked for editing and placing
+    ToggleMaterialEditor,
+    PickMaterial,
+    // select and change the fields of the edited material
+    EditorNextField,
+    EditorPreviousField,
+    EditorIncrease,
+    EditorDecrease,
+    // write the materials to settings/materials.ron
+    SaveMaterials,
+    // placing paints the material of the voxel under the crosshair and removing picks it
+    TogglePaint,
+    // octree node bounds overlay and the deepest level it shows
+    ToggleOctreeOverlay,
+    OverlayDepthUp,
+    OverlayDepthDown,
+    // palette, camera settings and stats panels
+    ToggleUi,
+    // releases the mouse to click the ui, or grabs it again for looking around
+    ToggleMouseGrab,
+    // hold to orbit or pan with the mouse in orbit mode
+    OrbitDrag,
+    PanDrag,
+    DebugUpdate,
+}
+
+impl Action {
+    /// Actions that trigger again on key repeat, the ones that step through something. Held actions such as
+    /// zooming keep running while a key repeats, so they do not need repeats
+    pub fn repeats(&self) -> bool {
+        matches!(self,
+            Action::PlaceVoxel | Action::RemoveVoxel
+            | Action::NextVoxel | Action::PreviousVoxel
+            | Action::EditorNextField | Action::EditorPreviousField | Action::EditorIncrease | Action::EditorDecrease
+            | Action::OverlayDepthUp | Action::OverlayDepthDown
+            | Action::NextDebugView
+        )
+    }
+}
+
+/// Mouse button ids as reported by device events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    Other(u32),
+}
+
+impl MouseButton {
+    pub fn from_id(id: u32) -> MouseButton {
+        match id {
+            1 => MouseButton::Left,
+            2 => MouseButton::Middle,
+            3 => MouseButton::Right,
+            other => MouseButton::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Binding {
+    pub action: Action,
+    pub input: Input,
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub shift: bool,
+    #[serde(default)]
+    pub alt: bool,
+}
+
+impl Binding {
+    fn new(action: Action, input: Input) -> Binding {
+        Binding { action, input, ctrl: false, shift: false, alt: false }
+    }
+
+    fn modifier_count(&self) -> usize {
+        self.ctrl as usize + self.shift as usize + self.alt as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Modifiers {
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+}
+
+impl Modifiers {
+    fn from_keys(keys: &[VirtualKeyCode]) -> Modifiers {
+        use VirtualKeyCode::*;
+        Modifiers {
+            ctrl: keys.contains(&LControl) || keys.contains(&RControl),
+            shift: keys.contains(&LShift) || keys.contains(&RShift),
+            alt: keys.contains(&LAlt) || keys.contains(&RAlt),
+        }
+    }
+
+    fn satisfies(&self, binding: &Binding) -> bool {
+        (!binding.ctrl || self.ctrl) && (!binding.shift || self.shift) && (!binding.alt || self.alt)
+    }
+}
+
+/// Maps keys and mouse buttons to actions, loaded from settings/input.ron
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct InputBindings {
+    pub bindings: Vec<Binding>,
+}
+
+impl InputBindings {
+    pub fn from_resources(res: &Resources, name: &str) -> Result<InputBindings, String> {
+        let bytes = res.load_buffer(name)
+            .map_err(|e| format!("Error loading resource {}: {:?}", name, e))?;
+        ron::de::from_bytes(&bytes[0..])
+            .map_err(|e| format!("Error parsing {}: {}", name, e))
+    }
+
+    /// Actions triggered by the currently held keys and mouse buttons.
+    /// When several bindings share an input only the ones with the most modifiers are used, so ctrl + 1
+    /// does not also trigger 1. Modifier keys that are used as modifiers do not trigger their own bindings
+    pub fn active_actions(&self, keys: &[VirtualKeyCode], buttons: &[u32]) -> Vec<Action> {
+        let held: Vec<Input> = keys.iter().map(|k| Input::Key(*k))
+            .chain(buttons.iter().map(|b| Input::Mouse(MouseButton::from_id(*b))))
+            .collect();
+        let modifiers = Modifiers::from_keys(keys);
+
+        let mut matched = Vec::<&Binding>::with_capacity(held.len());
+        for input in held.iter() {
+            let candidates = self.bindings.iter()
+                .filter(|b| b.input == *input && modifiers.satisfies(b));
+            let most_specific = candidates.clone().map(|b| b.modifier_count()).max();
+            matched.extend(candidates.filter(|b| Some(b.modifier_count()) == most_specific));
+        }
+
+        let used = matched.iter().fold(Modifiers::default(), |used, b| Modifiers {
+            ctrl: used.ctrl || b.ctrl,
+            shift: used.shift || b.shift,
+            alt: used.alt || b.alt,
+        });
+        let mut actions = Vec::<Action>::with_capacity(matched.len());
+        for binding in matched {
+            if is_used_modifier(&binding.input, &used) || actions.contains(&binding.action) {
+                continue;
+            }
+            actions.push(binding.action);
+        }
+        actions
+    }
+}
+
+/// Held keys and buttons built from app events, and the actions they trigger
+#[derive(Debug, Default)]
+pub struct InputState {
+    keys: Vec<VirtualKeyCode>,
+    buttons: Vec<u32>,
+    held: Vec<Action>,
+    // actions that started (or repeated) since the last end_frame
+    pressed: Vec<Action>,
+    // accumulated since the last end_frame
+    mouse_delta: (f32, f32),
+    scroll_delta: f32,
+}
+
+impl InputState {
+    /// Apply an input event, other events are ignored
+    pub fn handle(&mut self, event: &AppEvent, bindings: &InputBindings) {
+        match *event {
+            AppEvent::KeyPressed(key) => {
+                if self.keys.contains(&key) {
+                    // only the repeated key can trigger, not everything else that is held
+                    let mut keys: Vec<VirtualKeyCode> = self.keys.iter()
+                        .filter(|k| is_modifier(**k))
+                        .copied()
+                        .collect();
+                    keys.push(key);
+                    let repeated = bindings.active_actions(&keys, &[]);
+                    self.pressed.extend(repeated.into_iter().filter(|a| a.repeats()));
+                } else {
+                    self.keys.push(key);
+                    self.update_actions(bindings);
+                }
+            },
+            AppEvent::KeyReleased(key) => {
+                self.keys.retain(|k| *k != key);
+                self.update_actions(bindings);
+            },
+            AppEvent::ButtonPressed(button) => {
+                if !self.buttons.contains(&button) {
+                    self.buttons.push(button);
+                }
+                self.update_actions(bindings);
+            },
+            AppEvent::ButtonReleased(button) => {
+                self.buttons.retain(|b| *b != button);
+                self.update_actions(bindings);
+            },
+            AppEvent::MouseMoved(x, y) => {
+                self.mouse_delta = (self.mouse_delta.0 + x, self.mouse_delta.1 + y);
+            },
+            AppEvent::Scrolled(steps) => self.scroll_delta += steps,
+            AppEvent::FocusLost => {
+                self.keys.clear();
+                self.buttons.clear();
+                self.update_actions(bindings);
+            },
+            _ => { }
+        }
+    }
+
+    /// Recompute held actions, should be called when the bindings change
+    pub fn update_actions(&mut self, bindings: &InputBindings) {
+        let active = bindings.active_actions(&self.keys, &self.buttons);
+        for action in active.iter() {
+            if !self.held.contains(action) {
+                self.pressed.push(*action);
+            }
+        }
+        self.held = active;
+    }
+
+    /// Actions that are currently held
+    pub fn held(&self) -> &[Action] {
+        &self.held
+    }
+
+    /// Actions that started since last frame in the order they happened, may contain duplicates
+    pub fn pressed(&self) -> &[Action] {
+        &self.pressed
+    }
+
+    pub fn mouse_delta(&self) -> (f32, f32) {
+        self.mouse_delta
+    }
+
+    pub fn scroll_delta(&self) -> f32 {
+        self.scroll_delta
+    }
+
+    /// Clear everything that is accumulated per frame
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll_delta = 0.0;
+    }
+}
+
+fn is_modifier(key: VirtualKeyCode) -> bool {
+    use VirtualKeyCode::*;
+    matches!(key, LControl | RControl | LShift | RShift | LAlt | RAlt)
+}
+
+fn is_used_modifier(input: &Input, used: &Modifiers) -> bool {
+    use VirtualKeyCode::*;
+    match input {
+        Input::Key(LControl) | Input::Key(RControl) => used.ctrl,
+        Input::Key(LShift) | Input::Key(RShift) => used.shift,
+        Input::Key(LAlt) | Input::Key(RAlt) => used.alt,
+        _ => false,
+    }
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use VirtualKeyCode::*;
+        let number_row = [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+
+        let mut bindings = vec![
+            Binding::new(Action::MoveForward, Input::Key(W)),
+            Binding::new(Action::MoveBack, Input::Key(S)),
+            Binding::new(Action::MoveLeft, Input::Key(A)),
+            Binding::new(Action::MoveRight, Input::Key(D)),
+            Binding::new(Action::MoveUp, Input::Key(Space)),
+            Binding::new(Action::MoveDown, Input::Key(LControl)),
+            Binding::new(Action::Sprint, Input::Key(LShift)),
+            Binding::new(Action::RollLeft, Input::Key(Q)),
+            Binding::new(Action::RollRight, Input::Key(E)),
+            Binding::new(Action::ZoomIn, Input::Key(Z)),
+            Binding::new(Action::ZoomOut, Input::Key(X)),
+            Binding::new(Action::PlaceVoxel, Input::Mouse(MouseButton::Left)),
+            Binding::new(Action::RemoveVoxel, Input::Mouse(MouseButton::Right)),
+            Binding::new(Action::NextVoxel, Input::Key(Period)),
+            Binding::new(Action::PreviousVoxel, Input::Key(Comma)),
+            Binding::new(Action::AddKeyframe, Input::Key(K)),
+            Binding::new(Action::ClearPath, Input::Key(J)),
+            Binding::new(Action::PlayPath, Input::Key(P)),
+            Binding { shift: true, ..Binding::new(Action::CapturePath, Input::Key(P)) },
+            Binding::new(Action::ToggleOrbit, Input::Key(O)),
+            Binding::new(Action::FocusOrbit, Input::Key(F)),
+            Binding::new(Action::ToggleTurntable, Input::Key(T)),
+            Binding::new(Action::ToggleWalk, Input::Key(V)),
+            Binding::new(Action::ToggleFullscreen, Input::Key(F11)),
+            Binding::new(Action::ToggleProfiler, Input::Key(F3)),
+            Binding::new(Action::NextDebugView, Input::Key(F4)),
+            Binding::new(Action::ToggleOctreeOverlay, Input::Key(F5)),
+            Binding::new(Action::ToggleSmoothNormals, Input::Key(N)),
+            Binding::new(Action::ToggleMaterialEditor, Input::Key(F6)),
+            Binding::new(Action::PickMaterial, Input::Key(I)),
+            Binding::new(Action::EditorNextField, Input::Key(Down)),
+            Binding::new(Action::EditorPreviousField, Input::Key(Up)),
+            Binding::new(Action::EditorIncrease, Input::Key(Right)),
+            Binding::new(Action::EditorDecrease, Input::Key(Left)),
+            Binding { ctrl: true, ..Binding::new(Action::SaveMaterials, Input::Key(S)) },
+            Binding::new(Action::TogglePaint, Input::Key(B)),
+            Binding::new(Action::OverlayDepthUp, Input::Key(RBracket)),
+            Binding::new(Action::OverlayDepthDown, Input::Key(LBracket)),
+            Binding::new(Action::ToggleUi, Input::Key(F2)),
+            Binding::new(Action::ToggleMouseGrab, Input::Key(Tab)),
+            Binding::new(Action::OrbitDrag, Input::Mouse(MouseButton::Left)),
+            Binding::new(Action::PanDrag, Input::Mouse(MouseButton::Middle)),
+            Binding::new(Action::DebugUpdate, Input::Key(G)),
+        ];
+        for (i, key) in number_row.iter().enumerate() {
+            let input = Input::Key(*key);
+            bindings.push(Binding::new(Action::SelectVoxel(i as u32), input));
+            bindings.push(Binding { ctrl: true, ..Binding::new(Action::JumpToBookmark(i as u8 + 1), input) });
+            bindings.push(Binding { ctrl: true, shift: true, ..Binding::new(Action::StoreBookmark(i as u8 + 1), input) });
+        }
+
+        Self { bindings }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VirtualKeyCode::*;
+
+    fn shipped() -> InputBindings {
+        ron::de::from_str(include_str!("../../assets/settings/input.ron")).unwrap()
+    }
+
+    #[test]
+    fn most_specific_binding_wins() {
+        for bindings in [InputBindings::default(), shipped()].iter() {
+            assert_eq!(bindings.active_actions(&[Key1], &[]), vec![Action::SelectVoxel(0)]);
+            assert_eq!(bindings.active_actions(&[LControl, Key1], &[]), vec![Action::JumpToBookmark(1)]);
+            assert_eq!(bindings.active_actions(&[Key1, RControl], &[]), vec![Action::JumpToBookmark(1)]);
+            assert_eq!(bindings.active_actions(&[LControl, LShift, Key1], &[]), vec![Action::StoreBookmark(1)]);
+        }
+    }
+
+    #[test]
+    fn used_modifiers_do_not_trigger_their_bindings() {
+        let bindings = InputBindings::default();
+        // control alone moves down, but not while it is the modifier of a binding
+        assert_eq!(bindings.active_actions(&[LControl], &[]), vec![Action::MoveDown]);
+        assert_eq!(bindings.active_actions(&[LControl, S], &[]), vec![Action::SaveMaterials]);
+        assert_eq!(bindings.active_actions(&[S], &[]), vec![Action::MoveBack]);
+    }
+
+    #[test]
+    fn repeats_trigger_stepping_actions() {
+        let bindings = InputBindings::default();
+        let mut state = InputState::default();
+        state.handle(&AppEvent::KeyPressed(Period), &bindings);
+        state.handle(&AppEvent::KeyPressed(W), &bindings);
+        state.end_frame();
+        // a repeat of the held period steps again, the held w does not trigger
+        state.handle(&AppEvent::KeyPressed(Period), &bindings);
+        state.handle(&AppEvent::KeyPressed(Period), &bindings);
+        assert_eq!(state.pressed(), &[Action::NextVoxel, Action::NextVoxel][..]);
+        assert!(state.held().contains(&Action::MoveForward));
+    }
+
+    // Key6 selects voxel 5 like the other keys of the number row
+    #[test]
+    fn number_row_selects_matching_voxel() {
+        let keys = [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+        for bindings in [InputBindings::default(), shipped()].iter() {
+            for (i, key) in keys.iter().enumerate() {
+                assert_eq!(bindings.active_actions(&[*key], &[]), vec![Action::SelectVoxel(i as u32)]);
+            }
+        }
+    }
+}
@@ -1,7 +1,16 @@
 use cgmath::Vector3;
 
+pub mod app_event;
+pub mod benchmark;
+pub mod camera_path;
 pub mod chronos;
+pub mod dynamic_resolution;
+pub mod frame_capture;
+pub mod input;
 pub mod ply_point_loader;
+pub mod profiler;
+pub mod ui_input;
+pub mod walk;
 
 pub enum Direction {
     Front,
@@ -0,0 +1,166 @@
+use std::{collections::VecDeque, fs::File, io::{BufWriter, Write}, path::Path};
+
+use crate::renderer::gpu_timer::{GpuPass, QUERY_FRAMES};
+
+// about two seconds at 60 fps
+const ROLLING_SAMPLES: usize = 120;
+
+/// Min, average and max over the latest samples
+#[derive(Debug, Default)]
+pub struct RollingStats {
+    samples: VecDeque<f32>,
+}
+
+impl RollingStats {
+    pub fn push(&mut self, sample: f32) {
+        if self.samples.len() == ROLLING_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn min(&self) -> Option<f32> {
+        self.samples.iter().copied().reduce(f32::min)
+    }
+
+    pub fn avg(&self) -> Option<f32> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(self.samples.iter().sum::<f32>() / self.samples.len() as f32)
+    }
+
+    pub fn max(&self) -> Option<f32> {
+        self.samples.iter().copied().reduce(f32::max)
+    }
+}
+
+// a csv row that is written when the gpu timings of its frame have arrived
+struct CsvRow {
+    frame: usize,
+    frame_ms: f32,
+    passes: Vec<Option<f32>>,
+}
+
+/// Frame and gpu pass timings in milliseconds, shown in an overlay and optionally logged as csv
+pub struct Profiler {
+    frame: usize,
+    cpu_frame: RollingStats,
+    passes: Vec<RollingStats>,
+    csv: Option<BufWriter<File>>,
+    csv_rows: VecDeque<CsvRow>,
+}
+
+impl Profiler {
+    pub fn new(csv_path: Option<&Path>) -> Result<Profiler, String> {
+        let csv = match csv_path {
+            Some(path) => {
+                let file = File::create(path)
+                    .map_err(|e| format!("Failed to create profile log {:?}: {}", path, e))?;
+                let mut writer = BufWriter::new(file);
+                let header: Vec<&str> = GpuPass::ALL.iter().map(|p| p.name()).collect();
+                writeln!(writer, "frame,frame_ms,{}", header.join(","))
+                    .map_err(|e| format!("Failed to write profile log {:?}: {}", path, e))?;
+                Some(writer)
+            },
+            None => None,
+        };
+
+        Ok(Profiler {
+            frame: 0,
+            cpu_frame: RollingStats::default(),
+            passes: GpuPass::ALL.iter().map(|_| RollingStats::default()).collect(),
+            csv,
+            csv_rows: VecDeque::new(),
+        })
+    }
+
+    /// Record a frame and the gpu timings that became available, which belong to earlier frames. A csv row is written
+    /// once every timing of its frame has arrived, and passes that did not run are left empty
+    pub fn record_frame(&mut self, frame_ms: f32, gpu_timings: &[(usize, GpuPass, f32)]) {
+        self.cpu_frame.push(frame_ms);
+        for (_, pass, ms) in gpu_timings {
+            self.passes[*pass as usize].push(*ms);
+        }
+
+        if self.csv.is_some() {
+            self.csv_rows.push_back(CsvRow { frame: self.frame, frame_ms, passes: vec![None; GpuPass::ALL.len()] });
+            for (frame, pass, ms) in gpu_timings {
+                if let Some(row) = self.csv_rows.iter_mut().find(|row| row.frame == *frame) {
+                    row.passes[*pass as usize] = Some(*ms);
+                }
+            }
+        }
+        self.frame += 1;
+
+        // the timer has returned every timing of frames this old
+        while matches!(self.csv_rows.front(), Some(row) if row.frame + QUERY_FRAMES <= self.frame) {
+            if let Some(row) = self.csv_rows.pop_front() {
+                self.write_row(&row);
+            }
+        }
+    }
+
+    fn write_row(&mut self, row: &CsvRow) {
+        if let Some(writer) = &mut self.csv {
+            let columns: Vec<String> = row.passes.iter()
+                .map(|ms| ms.map_or(String::new(), |ms| format!("{:.4}", ms)))
+                .collect();
+            if let Err(e) = writeln!(writer, "{},{:.4},{}", row.frame + 1, row.frame_ms, columns.join(",")) {
+                eprintln!("Failed to write profile log: {}, logging stopped", e);
+                self.csv = None;
+            }
+        }
+    }
+
+    pub fn pass(&self, pass: GpuPass) -> &RollingStats {
+        &self.passes[pass as usize]
+    }
+
+    /// Frames per second from the average frame time
+    pub fn fps(&self) -> Option<f32> {
+        self.cpu_frame.avg().filter(|ms| *ms > 0.0).map(|ms| 1000.0 / ms)
+    }
+
+    /// Overlay window with a row for the frame time and each gpu pass
+    pub fn show(&self, ctx: &egui::Context) {
+        let format = |v: Option<f32>| match v {
+            Some(ms) => format!("{:.3}", ms),
+            None => "-".to_string(),
+        };
+
+        egui::Window::new("Profiler")
+            .default_pos(egui::pos2(8.0, 8.0))
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("profiler_timings").striped(true).show(ui, |ui| {
+                    ui.label("ms");
+                    ui.label("min");
+                    ui.label("avg");
+                    ui.label("max");
+                    ui.end_row();
+
+                    let mut row = |name: &str, stats: &RollingStats| {
+                        ui.label(name);
+                        ui.monospace(format(stats.min()));
+                        ui.monospace(format(stats.avg()));
+                        ui.monospace(format(stats.max()));
+                        ui.end_row();
+                    };
+                    row("frame", &self.cpu_frame);
+                    for pass in GpuPass::ALL.iter() {
+                        row(pass.name(), self.pass(*pass));
+                    }
+                });
+            });
+    }
+}
+
+impl Drop for Profiler {
+    // rows of the last frames are written without the gpu timings that did not arrive
+    fn drop(&mut self) {
+        while let Some(row) = self.csv_rows.pop_front() {
+            self.write_row(&row);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use super::{app_event::AppEvent, input::MouseButton};
+
+/// Pointer events for the egui overlay, collected from app events while the mouse is not grabbed by the camera
+#[derive(Default)]
+pub struct UiInput {
+    // cursor position in points, none until the cursor has moved inside the window
+    cursor: Option<egui::Pos2>,
+    events: Vec<egui::Event>,
+}
+
+impl UiInput {
+    /// Apply a mouse event, other events are ignored. pixels_per_point converts the cursor position from physical
+    /// pixels to points
+    pub fn handle(&mut self, event: &AppEvent, pixels_per_point: f32) {
+        match *event {
+            AppEvent::CursorMoved(x, y) => {
+                let pos = egui::pos2(x, y) / pixels_per_point;
+                self.cursor = Some(pos);
+                self.events.push(egui::Event::PointerMoved(pos));
+            },
+            AppEvent::ButtonPressed(id) | AppEvent::ButtonReleased(id) => {
+                if let (Some(pos), Some(button)) = (self.cursor, pointer_button(id)) {
+                    self.events.push(egui::Event::PointerButton {
+                        pos,
+                        button,
+                        pressed: matches!(event, AppEvent::ButtonPressed(_)),
+                        modifiers: egui::Modifiers::default(),
+                    });
+                }
+            },
+            // positive steps are scrolls away from the user, which move the content down
+            AppEvent::Scrolled(steps) => self.events.push(egui::Event::MouseWheel {
+                unit: egui::MouseWheelUnit::Line,
+                delta: egui::vec2(0.0, steps),
+                modifiers: egui::Modifiers::default(),
+            }),
+            AppEvent::FocusLost => self.release(),
+            _ => { }
+        }
+    }
+
+    /// The pointer left the ui, for example because the camera grabbed the mouse
+    pub fn release(&mut self) {
+        self.cursor = None;
+        self.events.push(egui::Event::PointerGone);
+    }
+
+    /// Drop the collected events, frames without ui should call this so that clicks are not replayed later
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Input of the next ui frame, the collected events are moved into it
+    pub fn raw_input(&mut self, screen_size: (i32, i32), pixels_per_point: f32, time: f64) -> egui::RawInput {
+        let mut raw_input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(screen_size.0 as f32, screen_size.1 as f32) / pixels_per_point
+            )),
+            time: Some(time),
+            events: std::mem::take(&mut self.events),
+            ..Default::default()
+        };
+        raw_input.viewports.entry(egui::ViewportId::ROOT).or_default().native_pixels_per_point = Some(pixels_per_point);
+        raw_input
+    }
+}
+
+fn pointer_button(id: u32) -> Option<egui::PointerButton> {
+    match MouseButton::from_id(id) {
+        MouseButton::Left => Some(egui::PointerButton::Primary),
+        MouseButton::Middle => Some(egui::PointerButton::Middle),
+        MouseButton::Right => Some(egui::PointerButton::Secondary),
+        MouseButton::Other(_) => None,
+    }
+}
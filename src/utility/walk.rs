@@ -0,0 +1,227 @@
+use cgmath::{InnerSpace, Vector3, Zero};
+use serde::{Serialize, Deserialize};
+
+use crate::renderer::octree::{HostOctree, Octree};
+
+/// Player body used in walk mode, all lengths are in voxels of the deepest level and times in seconds. Placed blocks
+/// are leaves one level above the deepest, so they are 2 voxels wide
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct WalkSettings {
+    pub walk_speed: f32,
+    pub sprint_speed: f32,
+    pub jump_speed: f32,
+    pub gravity: f32,
+    pub body_width: f32,
+    pub body_height: f32,
+    // camera height above the feet
+    pub eye_height: f32,
+    // ledges up to this height are climbed without jumping
+    pub step_height: f32,
+}
+
+impl Default for WalkSettings {
+    fn default() -> Self {
+        Self {
+            walk_speed: 8.6,
+            sprint_speed: 11.2,
+            jump_speed: 17.0,
+            gravity: 56.0,
+            body_width: 1.2,
+            body_height: 3.6,
+            eye_height: 3.2,
+            step_height: 2.0,
+        }
+    }
+}
+
+/// What the player wants the body to do this frame
+pub struct WalkInput {
+    // horizontal direction to walk in (length up to 1)
+    pub direction: Vector3<f32>,
+    pub jump: bool,
+    pub sprint: bool,
+}
+
+/// Axis aligned box body that collides with the voxels of the host octree. The bottom of the octree is a floor that
+/// extends past its sides. Walk mode needs the camera up to be +y
+#[derive(Debug)]
+pub struct WalkBody {
+    // voxels per second
+    velocity: Vector3<f32>,
+    grounded: bool,
+}
+
+impl Default for WalkBody {
+    fn default() -> Self {
+        Self {
+            velocity: Vector3::zero(),
+            grounded: false,
+        }
+    }
+}
+
+impl WalkBody {
+    /// Move the body one step and return the new eye position in world space
+    pub fn step(&mut self, settings: &WalkSettings, octree: &Octree, eye: Vector3<f32>, input: &WalkInput, delta_time: f32) -> Vector3<f32> {
+        let block = octree.block_distance();
+        let feet = (eye - octree.min_point()) / block - Vector3::unit_y() * settings.eye_height;
+        let feet = self.step_feet(settings, &octree.host, feet, input, delta_time);
+        (feet + Vector3::unit_y() * settings.eye_height) * block + octree.min_point()
+    }
+
+    // move the feet, which are in voxels of the host octree, one step
+    fn step_feet(&mut self, settings: &WalkSettings, octree: &HostOctree, mut feet: Vector3<f32>, input: &WalkInput, delta_time: f32) -> Vector3<f32> {
+        // the largest step without passing through a voxel
+        const MAX_SUBSTEP: f32 = 0.45;
+        const TERMINAL_VELOCITY: f32 = 80.0;
+
+        let speed = if input.sprint { settings.sprint_speed } else { settings.walk_speed };
+        let horizontal = Vector3::new(input.direction.x, 0.0, input.direction.z);
+        let horizontal = if horizontal.magnitude2() > 1.0 { horizontal.normalize() } else { horizontal };
+        self.velocity.x = horizontal.x * speed;
+        self.velocity.z = horizontal.z * speed;
+
+        if input.jump && self.grounded {
+            self.velocity.y = settings.jump_speed;
+        }
+        self.velocity.y = (self.velocity.y - settings.gravity * delta_time).max(-TERMINAL_VELOCITY);
+
+        let movement = self.velocity * delta_time;
+        let substeps = (movement.x.abs().max(movement.y.abs()).max(movement.z.abs()) / MAX_SUBSTEP).ceil().max(1.0);
+        let substep = movement / substeps;
+
+        let was_grounded = self.grounded;
+        self.grounded = false;
+        for _ in 0..substeps as i32 {
+            // vertical first so that landing is resolved before walking into ledges
+            if !self.move_axis(settings, octree, &mut feet, 1, substep.y) && substep.y < 0.0 {
+                self.grounded = true;
+            }
+            for axis in [0, 2] {
+                let before = feet;
+                if !self.move_axis(settings, octree, &mut feet, axis, substep[axis]) && (was_grounded || self.grounded) {
+                    // try to climb a ledge instead of stopping
+                    let mut stepped = before + Vector3::unit_y() * settings.step_height;
+                    if !self.collides(settings, octree, &stepped) {
+                        stepped[axis] += substep[axis];
+                        if !self.collides(settings, octree, &stepped) {
+                            feet = stepped;
+                        }
+                    }
+                }
+            }
+        }
+        feet
+    }
+
+    // move feet along axis, on collision the body is placed against the voxel and false is returned
+    fn move_axis(&mut self, settings: &WalkSettings, octree: &HostOctree, feet: &mut Vector3<f32>, axis: usize, amount: f32) -> bool {
+        const EPSILON: f32 = 0.001;
+        if amount == 0.0 {
+            return true;
+        }
+
+        let mut moved = *feet;
+        moved[axis] += amount;
+        if !self.collides(settings, octree, &moved) {
+            *feet = moved;
+            return true;
+        }
+
+        // steps are shorter than a voxel, so the blocking voxel is the one the body moved into
+        let (min, max) = body_bounds(settings, &moved);
+        if amount > 0.0 {
+            feet[axis] = moved[axis] - (max[axis] - max[axis].floor()) - EPSILON;
+        } else {
+            feet[axis] = moved[axis] + (min[axis].floor() + 1.0 - min[axis]) + EPSILON;
+        }
+        self.velocity[axis] = 0.0;
+        false
+    }
+
+    fn collides(&self, settings: &WalkSettings, octree: &HostOctree, feet: &Vector3<f32>) -> bool {
+        let (min, max) = body_bounds(settings, feet);
+        let min = Vector3::new(min.x.floor() as i32, min.y.floor() as i32, min.z.floor() as i32);
+        let max = Vector3::new(max.x.floor() as i32, max.y.floor() as i32, max.z.floor() as i32);
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    // below the octree is the floor
+                    if y < 0 || octree.is_solid(Vector3::new(x, y, z)) {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    pub fn reset(&mut self) {
+        self.velocity = Vector3::zero();
+        self.grounded = false;
+    }
+}
+
+// min and max corner of the body in voxel space
+fn body_bounds(settings: &WalkSettings, feet: &Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let half_width = settings.body_width * 0.5;
+    let min = Vector3::new(feet.x - half_width, feet.y, feet.z - half_width);
+    let max = Vector3::new(feet.x + half_width, feet.y + settings.body_height, feet.z + half_width);
+    (min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::octree::{EMPTY, LEAF};
+
+    // 16 voxels along each axis, placed blocks are 2 voxels
+    fn tree(blocks: &[(i32, i32, i32)]) -> HostOctree {
+        let mut tree = HostOctree::new(vec![EMPTY; 16], 4, 1);
+        for (x, y, z) in blocks.iter() {
+            tree.apply_delta(Vector3::new(*x, *y, *z), LEAF, 1);
+        }
+        tree
+    }
+
+    fn walk(tree: &HostOctree, mut feet: Vector3<f32>, direction: Vector3<f32>, frames: usize) -> (WalkBody, Vector3<f32>) {
+        let settings = WalkSettings::default();
+        let mut body = WalkBody::default();
+        let input = WalkInput { direction, jump: false, sprint: false };
+        for _ in 0..frames {
+            feet = body.step_feet(&settings, tree, feet, &input, 1.0 / 60.0);
+        }
+        (body, feet)
+    }
+
+    #[test]
+    fn lands_on_blocks_and_the_floor() {
+        let tree = tree(&[(4, 0, 4)]);
+
+        let (body, feet) = walk(&tree, Vector3::new(5.0, 8.0, 5.0), Vector3::zero(), 120);
+        assert!(body.grounded);
+        assert!((feet.y - 2.0).abs() < 0.01, "{:?}", feet);
+
+        // nothing below, the bottom of the octree stops the fall
+        let (body, feet) = walk(&tree, Vector3::new(12.0, 8.0, 12.0), Vector3::zero(), 120);
+        assert!(body.grounded);
+        assert!(feet.y.abs() < 0.01, "{:?}", feet);
+    }
+
+    #[test]
+    fn stops_at_walls() {
+        // 4 voxels high, more than a step
+        let tree = tree(&[(4, 0, 4), (4, 2, 4)]);
+        let (_, feet) = walk(&tree, Vector3::new(1.0, 0.0, 5.0), Vector3::unit_x(), 60);
+        assert!((feet.x - (4.0 - 0.6)).abs() < 0.01, "{:?}", feet);
+        assert!(feet.y.abs() < 0.01, "{:?}", feet);
+    }
+
+    #[test]
+    fn steps_onto_a_placed_block() {
+        let tree = tree(&[(4, 0, 4)]);
+        let (_, feet) = walk(&tree, Vector3::new(1.0, 0.0, 5.0), Vector3::unit_x(), 30);
+        assert!(feet.x > 4.6, "{:?}", feet);
+        assert!((feet.y - 2.0).abs() < 0.01, "{:?}", feet);
+    }
+}